@@ -1,3 +1,6 @@
+// to_binary and from_binary are kept for cosmwasm-std 1.0
+#![allow(deprecated)]
+
 pub mod allowances;
 pub mod contract;
 pub mod enumerable;
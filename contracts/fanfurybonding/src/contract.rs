@@ -1,42 +1,55 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128, from_binary,
-    WasmMsg, WasmQuery, QueryRequest, Order, Addr, CosmosMsg, QuerierWrapper, Storage
+    attr, to_binary, Binary, BlockInfo, Coin, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128, from_binary,
+    WasmMsg, WasmQuery, QueryRequest, Order, Addr, CosmosMsg, QuerierWrapper, Storage, Reply, SubMsg
 };
 use cw2::{get_contract_version, set_contract_version};
-use crate::util::Denom;
+use cw20::Denom;
 use cw_storage_plus::Bound;
-use cw_utils::{maybe_addr};
 use crate::error::ContractError;
 use crate::msg::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, BondStateResponse, BondingRecord, AllBondStateResponse
+    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, BondStateResponse, AllBondStateResponse,
+    ClaimableBond, ClaimableResponse, BondPriceResponse, MarketsResponse, ReceiveMsg, InventoryResponse,
+    BondQuoteResponse, EarlyUnbondPreviewResponse, BondNftResponse, StakingResponse, StakingHookMsg,
+    StatsResponse, MarketStatsResponse, MarketLimitsResponse, RemainingCapacityResponse,
+    PendingWithdrawResponse, ClaimSettingsResponse, KeeperTipResponse,
+    PolResponse, PoolExecuteMsg, PoolInfoResponse, BondHistoryResponse, ReferralResponse,
+    TiersResponse
 };
-use reqwest;
-use serde::Deserialize;
-
-struct CoinGeckoPriceResponse {
-    #[serde(rename = "usd")]
-    usd_price: f64,
-    // Add other fields if needed
-}
-
+use crate::oracle;
+use crate::limits;
+use crate::pricing;
+use crate::stats;
 use crate::state::{
-    Config, CONFIG, BONDING
+    Config, CONFIG, LEGACY_CONFIG, PriceSource, BondingRecord, bonding, next_bond_id, Market, MARKETS, MARKET_COUNT,
+    INVENTORY, EarlyUnbondConfig, EARLY_UNBOND, PenaltyDestination, BondNft, BOND_NFT, BOND_COUNT, STAKING,
+    STATS, DENOM_STATS, MARKET_STATS, MarketLimits, MARKET_LIMITS, ALLOWLIST,
+    WithdrawProposal, PENDING_WITHDRAW, WITHDRAW_DELAY, DEFAULT_WITHDRAW_DELAY, MIN_WITHDRAW_DELAY,
+    ClaimSettings, CLAIM_SETTINGS, CLAIM_OPERATORS, KEEPER_TIP,
+    POL_RATIO, POL_STATS, BondHistoryEntry, BondKind, bond_history,
+    REFERRAL_SHARE, REFERRALS, BondTier, TIERS
 };
-use cw20::Balance;
+use cw20::{Balance, Cw20ExecuteMsg, Cw20ReceiveMsg, Expiration};
+use cw721::{Cw721QueryMsg, OwnerOfResponse, TokensResponse};
+use cw721_base::{ExecuteMsg as Cw721ExecuteMsg, Extension, InstantiateMsg as Cw721InstantiateMsg, MintMsg};
+use cw_utils::parse_reply_instantiate_data;
 use crate::util;
-use crate::util::{NORMAL_DECIMAL, THOUSAND};
-use wasmswap::msg::{QueryMsg as WasmswapQueryMsg, Token1ForToken2PriceResponse, Token2ForToken1PriceResponse};
+use crate::util::THOUSAND;
+use wasmswap::msg::{
+    InfoResponse as WasmswapInfoResponse, QueryMsg as WasmswapQueryMsg, Token1ForToken2PriceResponse
+};
 // Version info, for migration info
 const CONTRACT_NAME: &str = "fanfurybonding";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const INSTANTIATE_BOND_NFT_REPLY_ID: u64 = 0;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    info: MessageInfo,
+    _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -46,13 +59,19 @@ pub fn instantiate(
         pool_address: msg.pool_address,
         treasury_address: msg.treasury_address,
         fury_token_address: msg.fury_token_address,
-        lock_days: msg.lock_days,
+        term: msg.term,
         discount: msg.discount,
         usdc_denom: msg.usdc_denom,
         is_native_bonding: msg.is_native_bonding,
         tx_fee: msg.tx_fee,
         platform_fee: msg.platform_fee,
         enabled: true,
+        price_source: msg.price_source,
+        max_price_age: msg.max_price_age,
+        max_price_deviation: msg.max_price_deviation,
+        control_variable: msg.control_variable,
+        min_price: msg.min_price,
+        max_debt: msg.max_debt,
     };
     CONFIG.save(deps.storage, &config)?;
 
@@ -69,11 +88,35 @@ pub fn execute(
     match msg {
         ExecuteMsg::UpdateOwner{owner} => execute_update_owner(deps, env, info, owner),
         ExecuteMsg::UpdateEnabled{enabled} => execute_update_enabled(deps, env, info, enabled),
-        ExecuteMsg::UpdateConfig{lock_days, discount, tx_fee, platform_fee} => execute_update_config(deps, env, info, lock_days, discount, tx_fee, platform_fee),
-        ExecuteMsg::Bond {  } => execute_bond(deps, env, info),
-        ExecuteMsg::LpBond {address, amount} => execute_lp_bond(deps, env, info, address, amount),
-        ExecuteMsg::Unbond { index } => execute_unbond(deps, env, info, index),
-        ExecuteMsg::Withdraw { amount } => execute_withdraw(deps, env, info, amount)
+        ExecuteMsg::UpdateConfig{term, discount, tx_fee, platform_fee} => execute_update_config(deps, env, info, term, discount, tx_fee, platform_fee),
+        ExecuteMsg::UpdatePriceSource{price_source, max_price_age, max_price_deviation} => execute_update_price_source(deps, env, info, price_source, max_price_age, max_price_deviation),
+        ExecuteMsg::UpdateBondPricing{control_variable, min_price, max_debt} => execute_update_bond_pricing(deps, env, info, control_variable, min_price, max_debt),
+        ExecuteMsg::CreateMarket{asset, pool_address, is_lp, discount, vesting_term, capacity, start_time, end_time} => execute_create_market(deps, env, info, asset, pool_address, is_lp, discount, vesting_term, capacity, start_time, end_time),
+        ExecuteMsg::UpdateMarket{market_id, pool_address, discount, vesting_term, capacity, start_time, end_time} => execute_update_market(deps, env, info, market_id, pool_address, discount, vesting_term, capacity, start_time, end_time),
+        ExecuteMsg::CloseMarket{market_id} => execute_close_market(deps, env, info, market_id),
+        ExecuteMsg::UpdateMarketLimits{market_id, limits} => execute_update_market_limits(deps, env, info, market_id, limits),
+        ExecuteMsg::UpdateAllowlist{market_id, add, remove} => execute_update_allowlist(deps, env, info, market_id, add, remove),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Bond { market_id, min_payout, expiration, referrer, tier } => execute_bond(deps, env, info, market_id, min_payout, expiration, referrer, tier),
+        ExecuteMsg::LpBond {address, amount, lp_amount} => execute_lp_bond(deps, env, info, address, amount, lp_amount),
+        ExecuteMsg::UpdateEarlyUnbond { max_penalty, destination } => execute_update_early_unbond(deps, env, info, max_penalty, destination),
+        ExecuteMsg::Claim { bond_id, stake_on_claim } => execute_claim(deps, env, info, bond_id, stake_on_claim),
+        ExecuteMsg::EarlyUnbond { bond_id } => execute_early_unbond(deps, env, info, bond_id),
+        ExecuteMsg::ClaimAll { start_after, limit, stake_on_claim } => execute_claim_all(deps, env, info, start_after, limit, stake_on_claim),
+        ExecuteMsg::UpdateStaking { address } => execute_update_staking(deps, env, info, address),
+        ExecuteMsg::UpdateClaimOperator { operator, approved } => execute_update_claim_operator(deps, env, info, operator, approved),
+        ExecuteMsg::UpdateClaimSettings { keeper_claims, recipient } => execute_update_claim_settings(deps, env, info, keeper_claims, recipient),
+        ExecuteMsg::UpdateKeeperTip { tip } => execute_update_keeper_tip(deps, env, info, tip),
+        ExecuteMsg::UpdatePolRatio { pol_ratio } => execute_update_pol_ratio(deps, env, info, pol_ratio),
+        ExecuteMsg::UpdateReferralShare { referral_share } => execute_update_referral_share(deps, env, info, referral_share),
+        ExecuteMsg::UpdateTiers { tiers } => execute_update_tiers(deps, env, info, tiers),
+        ExecuteMsg::ClaimFor { owner, start_after, limit } => execute_claim_for(deps, env, info, owner, start_after, limit),
+        ExecuteMsg::CreateBondNft { code_id, name, symbol } => execute_create_bond_nft(deps, env, info, code_id, name, symbol),
+        ExecuteMsg::SyncBondOwner { bond_id } => execute_sync_bond_owner(deps, env, info, bond_id),
+        ExecuteMsg::UpdateWithdrawDelay { delay } => execute_update_withdraw_delay(deps, env, info, delay),
+        ExecuteMsg::ProposeWithdraw { denom, amount, recipient } => execute_propose_withdraw(deps, env, info, denom, amount, recipient),
+        ExecuteMsg::ExecuteWithdraw {} => execute_execute_withdraw(deps, env, info),
+        ExecuteMsg::CancelWithdraw {} => execute_cancel_withdraw(deps, env, info),
     }
 }
 
@@ -102,14 +145,14 @@ pub fn check_owner(
 
 pub fn execute_update_owner(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
     owner: Addr
 ) -> Result<Response, ContractError> {
 
     check_owner(deps.storage, info.sender.clone())?;
 
-    let mut cfg = CONFIG.load(deps.storage)?;
+    let cfg = CONFIG.load(deps.storage)?;
 
     CONFIG.save(deps.storage, &cfg)?;
 
@@ -123,7 +166,7 @@ pub fn execute_update_owner(
 
 pub fn execute_update_enabled(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
     enabled: bool
 ) -> Result<Response, ContractError> {
@@ -145,18 +188,25 @@ pub fn execute_update_enabled(
 
 pub fn execute_update_config(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    lock_days: u64,
+    term: u64,
     discount: u64,
     tx_fee: u64,
     platform_fee: u64
 ) -> Result<Response, ContractError> {
-    check_owner(deps.storage, info.sender.clone())?;
-
     let mut cfg = CONFIG.load(deps.storage)?;
 
-    cfg.lock_days = lock_days;
+    // the pool forwards its own config changes
+    if info.sender != cfg.owner && info.sender != cfg.pool_address {
+        return Err(ContractError::Unauthorized {})
+    }
+    if exceeds_thousand(&[tx_fee, platform_fee, discount]) {
+        return Err(ContractError::InvalidInput {})
+    }
+    check_fee_headroom(deps.storage, tx_fee + platform_fee)?;
+
+    cfg.term = term;
     cfg.discount = discount;
     cfg.tx_fee = tx_fee;
     cfg.platform_fee = platform_fee;
@@ -165,298 +215,1964 @@ pub fn execute_update_config(
     return Ok(Response::new()
         .add_attributes(vec![
             attr("action", "update_config"),
-            attr("lock_days", lock_days.to_string()),
+            attr("term", term.to_string()),
             attr("discount", discount.to_string()),
         ]));
 }
 
 
-
-pub fn execute_bond(
+pub fn execute_update_price_source(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    price_source: PriceSource,
+    max_price_age: u64,
+    max_price_deviation: u64
 ) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
 
-    check_enabled(deps.storage)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
 
+    cfg.price_source = price_source;
+    cfg.max_price_age = max_price_age;
+    cfg.max_price_deviation = max_price_deviation;
+    CONFIG.save(deps.storage, &cfg)?;
 
-    let cfg = CONFIG.load(deps.storage)?;
+    // make sure the new source can actually be read
+    oracle::query_fury_price(deps.as_ref(), &env, &cfg)?;
 
-    if !cfg.is_native_bonding {
-        return Err(ContractError::NotAllowedBondingType {  })
-    }
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_price_source"),
+            attr("max_price_age", max_price_age.to_string()),
+            attr("max_price_deviation", max_price_deviation.to_string()),
+        ]));
+}
 
-    let balance = Balance::from(info.funds);
+pub fn execute_update_bond_pricing(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    control_variable: u64,
+    min_price: Decimal,
+    max_debt: Option<Uint128>
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
 
-    let usdc_amount = util::get_amount_of_denom(balance, Denom::Native(cfg.usdc_denom.clone()))?;
+    let mut cfg = CONFIG.load(deps.storage)?;
 
-    if usdc_amount == Uint128::zero() {
-        return Err(ContractError::NativeInputZero {  })
-    }
-    let fee_amount = usdc_amount * Uint128::from(THOUSAND - cfg.tx_fee - cfg.platform_fee) / Uint128::from(THOUSAND);
-    let real_amount = usdc_amount - fee_amount;
+    cfg.control_variable = control_variable;
+    cfg.min_price = min_price;
+    cfg.max_debt = max_debt;
+    CONFIG.save(deps.storage, &cfg)?;
 
-    let token2_price_response: Token1ForToken2PriceResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: cfg.pool_address.clone().into(),
-        msg: to_binary(&WasmswapQueryMsg::Token1ForToken2Price {
-            token1_amount: real_amount
-        })?,
-    }))?;
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_bond_pricing"),
+            attr("control_variable", control_variable.to_string()),
+            attr("min_price", min_price.to_string()),
+        ]));
+}
 
-    let receiving_amount = token2_price_response.token2_amount * Uint128::from(THOUSAND) / Uint128::from(THOUSAND - cfg.discount);
+#[allow(clippy::too_many_arguments)]
+pub fn execute_create_market(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    asset: Denom,
+    pool_address: Option<Addr>,
+    is_lp: bool,
+    discount: u64,
+    vesting_term: u64,
+    capacity: Uint128,
+    start_time: u64,
+    end_time: u64
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
 
-    let mut messages:Vec<CosmosMsg> = vec![];
-    messages.push(util::transfer_token_message(Denom::Native(cfg.usdc_denom.clone()), usdc_amount, cfg.treasury_address.clone())?);
+    // lp markets only take the pool's own cw20 LP token
+    if is_lp && !matches!(asset, Denom::Cw20(_)) {
+        return Err(ContractError::TokenTypeMismatch {})
+    }
 
-    let mut list:Vec<BondingRecord> = BONDING.load(deps.storage, info.sender.clone()).unwrap_or(vec![]);
-    list.push(BondingRecord {
-        amount: receiving_amount,
-        timestamp: env.block.time.seconds() + 86400 * cfg.lock_days
-    });
-    BONDING.save(deps.storage, info.sender.clone(), &list)?;
+    let id = MARKET_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let market = Market {
+        id,
+        asset,
+        pool_address,
+        is_lp,
+        discount,
+        vesting_term,
+        capacity,
+        sold: Uint128::zero(),
+        start_time,
+        end_time,
+        closed: false,
+    };
+    validate_market(deps.as_ref(), &market)?;
 
+    MARKET_COUNT.save(deps.storage, &id)?;
+    MARKETS.save(deps.storage, id, &market)?;
 
     return Ok(Response::new()
         .add_attributes(vec![
-            attr("action", "bond"),
-            attr("bond_usdc_amount", real_amount),
-            attr("receiving_amount", receiving_amount),
-            attr("address", info.sender.clone()),
+            attr("action", "create_market"),
+            attr("market_id", id.to_string()),
+            attr("capacity", capacity),
         ]));
 }
 
-
-pub fn execute_lp_bond(
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_market(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    address: Addr,
-    amount: Uint128
+    market_id: u64,
+    pool_address: Option<Addr>,
+    discount: Option<u64>,
+    vesting_term: Option<u64>,
+    capacity: Option<Uint128>,
+    start_time: Option<u64>,
+    end_time: Option<u64>
 ) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
 
-    check_enabled(deps.storage)?;
-
-    let cfg = CONFIG.load(deps.storage)?;
-
-    if info.sender.clone() != cfg.pool_address {
-        return Err(ContractError::Unauthorized {  });
-    }
-
-    if amount == Uint128::zero() {
-        return Err(ContractError::Cw20InputZero {  })
-    }
-
-    if cfg.is_native_bonding {
-        return Err(ContractError::NotAllowedBondingType {  })
+    let mut market = load_market(deps.storage, market_id)?;
+    if pool_address.is_some() {
+        market.pool_address = pool_address;
     }
+    market.discount = discount.unwrap_or(market.discount);
+    market.vesting_term = vesting_term.unwrap_or(market.vesting_term);
+    market.capacity = capacity.unwrap_or(market.capacity);
+    market.start_time = start_time.unwrap_or(market.start_time);
+    market.end_time = end_time.unwrap_or(market.end_time);
+    validate_market(deps.as_ref(), &market)?;
 
-    // On lp bonding, the platform fee and tx fee is already stolen from swap contract
-    let receiving_amount = amount * Uint128::from(THOUSAND) / Uint128::from(THOUSAND - cfg.discount);
-    let mut list:Vec<BondingRecord> = BONDING.load(deps.storage, info.sender.clone()).unwrap_or(vec![]);
-    list.push(BondingRecord {
-        amount: receiving_amount,
-        timestamp: env.block.time.seconds() + 86400 * cfg.lock_days
-    });
-    BONDING.save(deps.storage, info.sender.clone(), &list)?;
+    MARKETS.save(deps.storage, market_id, &market)?;
 
     return Ok(Response::new()
         .add_attributes(vec![
-            attr("action", "lp_bond"),
-            attr("bond_fury_amount", amount),
-            attr("receiving_amount", receiving_amount),
-            attr("address", info.sender.clone()),
+            attr("action", "update_market"),
+            attr("market_id", market_id.to_string()),
         ]));
 }
 
-pub fn execute_unbond(
+pub fn execute_close_market(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
-    index: u64
+    market_id: u64
 ) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
 
-    check_enabled(deps.storage)?;
+    let mut market = load_market(deps.storage, market_id)?;
+    market.closed = true;
+    MARKETS.save(deps.storage, market_id, &market)?;
 
-    let cfg = CONFIG.load(deps.storage)?;
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "close_market"),
+            attr("market_id", market_id.to_string()),
+        ]));
+}
 
-    let mut list = BONDING.load(deps.storage, info.sender.clone())?;
+pub fn execute_update_market_limits(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    market_id: u64,
+    limits: Option<MarketLimits>
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+    load_market(deps.storage, market_id)?;
 
-    if list.len() >= index as usize {
-        return Err(ContractError::InvalidInput {  })
+    // no limits at all removes them
+    match limits.clone() {
+        Some(limits) => MARKET_LIMITS.save(deps.storage, market_id, &limits)?,
+        None => MARKET_LIMITS.remove(deps.storage, market_id),
     }
 
-    let (_index, record) =list.iter().enumerate().find(|(i, c)| i == &(index as usize)).unwrap();
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_market_limits"),
+            attr("market_id", market_id.to_string()),
+            attr("epoch_length", limits.map_or(0, |limits| limits.epoch_length).to_string()),
+        ]));
+}
 
-    // let record = list.clone().get(index as usize).unwrap();
-    if record.timestamp > env.block.time.seconds() {
-        return Err(ContractError::StillInBonding {})
-    }
+pub fn execute_update_allowlist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    market_id: u64,
+    add: Vec<Addr>,
+    remove: Vec<Addr>
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+    load_market(deps.storage, market_id)?;
 
-    let balance = Balance::from(info.funds);
+    for address in add.iter() {
+        let address = deps.api.addr_validate(address.as_str())?;
+        ALLOWLIST.save(deps.storage, (market_id, &address), &true)?;
+    }
+    for address in remove.iter() {
+        ALLOWLIST.remove(deps.storage, (market_id, address));
+    }
 
-    //calculate tx fee
-    let usdc_amount = util::get_amount_of_denom(balance, Denom::Native(cfg.usdc_denom.clone()))?;
-    let token2_price_response: Uint128 = query_native_token_price(real_amount)?;
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_allowlist"),
+            attr("market_id", market_id.to_string()),
+            attr("added", add.len().to_string()),
+            attr("removed", remove.len().to_string()),
+        ]));
+}
 
-// Example function for querying native token price
-    fn query_native_token_price(amount: Uint128) -> StdResult<Uint128> {
-    // Replace "YOUR_FANFURY_API_KEY" with your actual FanFury API key
-    let api_key = "YOUR_FANFURY_API_KEY";
-    let token_symbol = "FURY";  // Update with your native token symbol
+fn load_market(storage: &dyn Storage, market_id: u64) -> Result<Market, ContractError> {
+    MARKETS.may_load(storage, market_id)?.ok_or(ContractError::MarketNotFound { market_id })
+}
 
-    // Build the URL for the CoinGecko API request
-    let url = format!("https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies=usd", token_symbol);
+// Open markets and tiers must keep discount + fees below THOUSAND under the new fees
+fn check_fee_headroom(storage: &dyn Storage, fees: u64) -> Result<(), ContractError> {
+    for item in MARKETS.range(storage, None, None, Order::Ascending) {
+        let (_, market) = item?;
+        if !market.closed && exceeds_thousand(&[market.discount, fees]) {
+            return Err(ContractError::FeesTooHigh { discount: market.discount })
+        }
+    }
+    for tier in TIERS.may_load(storage)?.unwrap_or_default() {
+        if exceeds_thousand(&[tier.discount, fees]) {
+            return Err(ContractError::FeesTooHigh { discount: tier.discount })
+        }
+    }
+    Ok(())
+}
 
-    // Make the HTTP request
-    let response = reqwest::blocking::get(&url)
-        .map_err(|_| ContractError::ApiRequestFailed)?;
+// True when the per-mille amounts add up to THOUSAND or more, an overflowing sum included
+fn exceeds_thousand(amounts: &[u64]) -> bool {
+    match amounts.iter().try_fold(0u64, |sum, amount| sum.checked_add(*amount)) {
+        Some(sum) => sum >= THOUSAND,
+        None => true,
+    }
+}
 
-    // Parse the response JSON
-    let price_response: CoinGeckoPriceResponse = response.json().map_err(|_| ContractError::ApiParsingFailed)?;
+fn validate_market(deps: Deps, market: &Market) -> Result<(), ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if exceeds_thousand(&[market.discount, cfg.tx_fee, cfg.platform_fee])
+        || market.start_time >= market.end_time
+        || market.capacity < market.sold {
+        return Err(ContractError::InvalidInput {})
+    }
 
-    // Calculate the uFury price based on the amount and the retrieved USD price
-    let usd_price = price_response.usd_price;
-    let fury_price = (usd_price * amount.u128() as f64) as u128;
+    // only usdc and the config pool's own LP token may fall back to the config pool
+    let pool_address = match market.pool_address.clone() {
+        Some(pool_address) => pool_address,
+        None if market.is_lp || market.asset == Denom::Native(cfg.usdc_denom.clone()) => cfg.pool_address.clone(),
+        None => return Err(ContractError::MarketPoolRequired {}),
+    };
 
-    Ok(Uint128::from(fury_price))
-    }
-    if usdc_amount < token1_price_response.token1_amount * Uint128::from(cfg.platform_fee + cfg.tx_fee) / Uint128::from(THOUSAND) {
-        return Err(ContractError::InsufficientFee { })
+    // the pool has to trade the asset, or mint it for lp markets, against Fury
+    let info: PoolInfoResponse = deps.querier.query_wasm_smart(&pool_address, &WasmswapQueryMsg::Info {})?;
+    let asset_matches = if market.is_lp {
+        market.asset == Denom::Cw20(Addr::unchecked(info.lp_token_address))
+    } else {
+        market.asset == info.token1_denom
+    };
+    if !asset_matches || info.token2_denom != Denom::Cw20(cfg.fury_token_address) {
+        return Err(ContractError::MarketPoolMismatch {})
     }
+    Ok(())
+}
 
-    let fury_balance = util::get_native_balance(&cfg.usdc_denom, &env.contract.address)?;
-    if fury_balance < record.amount {
-        return Err(ContractError::InsufficientFury {})
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg
+) -> Result<Response, ContractError> {
+
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Fund {} => execute_fund(deps, info, sender, wrapper.amount),
+        ReceiveMsg::Bond { market_id, min_payout, expiration, referrer } => {
+            check_enabled(deps.storage)?;
+            check_expiration(&expiration, &env.block)?;
+            let market = load_market(deps.storage, market_id)?;
+            if market.asset != Denom::Cw20(info.sender.clone()) {
+                return Err(ContractError::UnacceptableToken {})
+            }
+            execute_market_bond(deps, env, sender, market, wrapper.amount, min_payout, referrer)
+        }
     }
+}
 
-    let mut messages:Vec<CosmosMsg> = vec![];
-    messages.push(util::transfer_token_message(Denom::Cw20(cfg.fury_token_address.clone()), record.amount, info.sender.clone())?);
-    messages.push(util::transfer_token_message(Denom::Native(cfg.usdc_denom.clone()), usdc_amount, cfg.treasury_address.clone())?);
+pub fn execute_fund(
+    deps: DepsMut,
+    info: MessageInfo,
+    sender: Addr,
+    amount: Uint128
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if info.sender != cfg.fury_token_address {
+        return Err(ContractError::UnacceptableToken {})
+    }
+    if amount.is_zero() {
+        return Err(ContractError::Cw20InputZero {})
+    }
 
-    let mut list = BONDING.load(deps.storage, info.sender.clone())?;
-    list.remove(index as usize);
-    BONDING.save(deps.storage, info.sender.clone(), &list)?;
+    let mut inventory = INVENTORY.may_load(deps.storage)?.unwrap_or_default();
+    inventory.free += amount;
+    INVENTORY.save(deps.storage, &inventory)?;
 
     return Ok(Response::new()
         .add_attributes(vec![
-            attr("action", "unbond"),
-            attr("receiving_amount", record.amount),
-            attr("address", info.sender.clone()),
+            attr("action", "fund"),
+            attr("amount", amount),
+            attr("free", inventory.free),
+            attr("address", sender),
         ]));
 }
 
-pub fn execute_withdraw(
-    deps: DepsMut,
+pub fn execute_bond(
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    amount: Uint128
+    market_id: Option<u64>,
+    min_payout: Option<Uint128>,
+    expiration: Option<Expiration>,
+    referrer: Option<Addr>,
+    tier: Option<u32>,
 ) -> Result<Response, ContractError> {
 
-    check_owner(deps.storage, info.sender.clone())?;
+    check_enabled(deps.storage)?;
+    check_expiration(&expiration, &env.block)?;
+
+    if let Some(market_id) = market_id {
+        // markets have their own discount and vesting term
+        if tier.is_some() {
+            return Err(ContractError::InvalidInput {})
+        }
+        let market = load_market(deps.storage, market_id)?;
+        let denom = match market.asset.clone() {
+            Denom::Native(denom) => denom,
+            Denom::Cw20(_) => return Err(ContractError::TokenTypeMismatch {})
+        };
+        let amount = util::get_amount_of_denom(Balance::from(info.funds), Denom::Native(denom))?;
+        return execute_market_bond(deps, env, info.sender, market, amount, min_payout, referrer);
+    }
 
     let cfg = CONFIG.load(deps.storage)?;
 
-    let fury_balance = util::get_native_balance(&cfg.usdc_denom, &env.contract.address)?;
-    if fury_balance < record.amount {
-        return Err(ContractError::InsufficientFury {})
+    if !cfg.is_native_bonding {
+        return Err(ContractError::NotAllowedBondingType {  })
     }
 
-    let mut messages:Vec<CosmosMsg> = vec![];
-    messages.push(util::transfer_token_message(Denom::Cw20(cfg.fury_token_address.clone()), amount, info.sender.clone())?);
+    let balance = Balance::from(info.funds);
+
+    let usdc_amount = util::get_amount_of_denom(balance, Denom::Native(cfg.usdc_denom.clone()))?;
+
+    let quote = quote_bond(deps.as_ref(), &env, None, tier, usdc_amount)?;
+    check_min_payout(min_payout, quote.payout)?;
+
+    let now = env.block.time.seconds();
+    pricing::add_debt(deps.storage, &cfg, now, quote.payout)?;
+
+    let vesting_term = quote.unlock_time - now;
+    let bond_id = save_bond(deps.storage, info.sender.clone(), None, tier, quote.payout, now, vesting_term)?;
+    let (referral_reward, referral_msgs) = save_referral_bond(deps.branch(), &cfg, referrer, &info.sender, None, &Denom::Native(cfg.usdc_denom.clone()), &quote, now, vesting_term)?;
+    let mut messages = proceeds_messages(deps.branch(), &cfg, &Denom::Native(cfg.usdc_denom.clone()), usdc_amount)?;
+    stats::record_bond(deps.storage, None, &Denom::Native(cfg.usdc_denom.clone()), quote.bond_amount, quote.fee_amount, quote.payout)?;
+    save_bond_history(deps.storage, bond_id, &info.sender, None, Denom::Native(cfg.usdc_denom.clone()), usdc_amount, &quote, now)?;
+    messages.extend(mint_bond_nft(deps.storage, bond_id, &info.sender)?);
+    messages.extend(referral_msgs);
+
 
     return Ok(Response::new()
+        .add_messages(messages)
         .add_attributes(vec![
-            attr("action", "withdraw"),
-            attr("receiving_amount", amount),
+            attr("action", "bond"),
+            attr("bond_id", bond_id.to_string()),
+            attr("bond_usdc_amount", quote.bond_amount),
+            attr("tier", tier.map_or(String::from("none"), |tier| tier.to_string())),
+            attr("discount", quote.discount.to_string()),
+            attr("receiving_amount", quote.payout),
+            attr("referral_reward", referral_reward),
             attr("address", info.sender.clone()),
         ]));
 }
 
-
-
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {}
-            => to_binary(&query_config(deps)?),
-        QueryMsg::BondState {address}
-            => to_binary(&query_bond_state(deps, address)?),
-        QueryMsg::AllBondState {start_after, limit}
-            => to_binary(&query_all_bond_state(deps, start_after, limit)?),
+fn check_expiration(
+    expiration: &Option<Expiration>,
+    block: &BlockInfo,
+) -> Result<(), ContractError> {
+    match expiration {
+        Some(e) => {
+            if e.is_expired(block) {
+                return Err(ContractError::MsgExpirationError {});
+            }
+            Ok(())
+        }
+        None => Ok(()),
     }
 }
 
-pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
-    Ok(ConfigResponse {
-        owner: cfg.owner,
-        pool_address: cfg.pool_address,
-        treasury_address: cfg.treasury_address,
-        fury_token_address: cfg.fury_token_address,
-        lock_days: cfg.lock_days,
-        discount: cfg.discount,
-        usdc_denom: cfg.usdc_denom,
-        is_native_bonding: cfg.is_native_bonding,
-        tx_fee: cfg.tx_fee,
-        platform_fee: cfg.platform_fee,
-        enabled: cfg.enabled
-    })
+fn check_min_payout(min_payout: Option<Uint128>, payout: Uint128) -> Result<(), ContractError> {
+    if let Some(min_payout) = min_payout {
+        if payout < min_payout {
+            return Err(ContractError::MinPayoutError { min_payout, payout });
+        }
+    }
+    Ok(())
 }
 
-pub fn query_bond_state(deps: Deps, address: Addr) -> StdResult<BondStateResponse> {
+// Prices a bond of `amount` input the same way execution does, without touching state
+fn quote_bond(
+    deps: Deps,
+    env: &Env,
+    market: Option<&Market>,
+    tier: Option<u32>,
+    amount: Uint128
+) -> Result<BondQuoteResponse, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
-    let list = BONDING.load(deps.storage, address.clone()).unwrap_or(vec![]);
+    let now = env.block.time.seconds();
 
-    Ok(BondStateResponse {
-        address,
-        list
-    })
-}
+    if amount.is_zero() {
+        return Err(ContractError::NativeInputZero {  })
+    }
 
-// settings for pagination
-const MAX_LIMIT: u32 = 30;
-const DEFAULT_LIMIT: u32 = 10;
+    let fee_amount = amount * Uint128::from(cfg.tx_fee + cfg.platform_fee) / Uint128::from(THOUSAND);
+    let bond_amount = amount - fee_amount;
+
+    let (asset, pool_address, mut base_discount, mut vesting_term, is_lp) = match market {
+        Some(market) => (
+            market.asset.clone(),
+            market.pool_address.clone().unwrap_or(cfg.pool_address.clone()),
+            market.discount,
+            market.vesting_term,
+            market.is_lp
+        ),
+        None => (
+            Denom::Native(cfg.usdc_denom.clone()),
+            cfg.pool_address.clone(),
+            cfg.discount,
+            cfg.term,
+            false
+        ),
+    };
 
-fn map_bonding(
-    item: StdResult<(Addr, Vec<BondingRecord>)>,
-) -> StdResult<BondStateResponse> {
-    item.map(|(address, list)| {
-        BondStateResponse {
-            address,
-            list
+    if let Some(tier) = tier {
+        if market.is_some() {
+            return Err(ContractError::InvalidInput {})
         }
+        let bond_tier = TIERS
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .get(tier as usize)
+            .cloned()
+            .ok_or(ContractError::TierNotFound { tier })?;
+        if bond_amount < bond_tier.min_amount {
+            return Err(ContractError::TierMinAmount { tier, min_amount: bond_tier.min_amount })
+        }
+        base_discount = bond_tier.discount;
+        vesting_term = bond_tier.term;
+    }
+
+    let fury_value = if is_lp {
+        oracle::query_lp_fury_value(deps, env, &cfg, &pool_address, &asset, bond_amount)?
+    } else {
+        let token2_price_response: Token1ForToken2PriceResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: pool_address.into(),
+            msg: to_binary(&WasmswapQueryMsg::Token1ForToken2Price {
+                token1_amount: bond_amount
+            })?,
+        }))?;
+        token2_price_response.token2_amount
+    };
+
+    if fury_value.is_zero() {
+        return Err(ContractError::NoLiquidity {})
+    }
+
+    let discount = pricing::effective_discount(&cfg, base_discount, pricing::current_debt(deps.storage, &cfg, now)?);
+    let mut payout = fury_value * Uint128::from(THOUSAND) / Uint128::from(THOUSAND - discount);
+    if asset == Denom::Native(cfg.usdc_denom.clone()) {
+        payout = pricing::apply_min_price(&cfg, bond_amount, payout);
+    }
+
+    Ok(BondQuoteResponse {
+        fee_amount,
+        bond_amount,
+        market_price: Decimal::from_ratio(bond_amount, fury_value),
+        tier,
+        discount,
+        payout,
+        unlock_time: now + vesting_term,
     })
 }
 
 
-fn query_all_bond_state(
-    deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<AllBondStateResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let addr = maybe_addr(deps.api, start_after)?;
-    let start = addr.map(|addr| Bound::exclusive(addr));
+pub fn execute_lp_bond(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: Addr,
+    amount: Uint128,
+    lp_amount: Uint128
+) -> Result<Response, ContractError> {
 
-    let list:StdResult<Vec<_>> = BONDING
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|item| map_bonding(item))
-        .collect();
+    check_enabled(deps.storage)?;
 
-    Ok(AllBondStateResponse { list: list? })
-}
+    let cfg = CONFIG.load(deps.storage)?;
+
+    if info.sender.clone() != cfg.pool_address {
+        return Err(ContractError::Unauthorized {  });
+    }
 
+    if amount == Uint128::zero() {
+        return Err(ContractError::Cw20InputZero {  })
+    }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    let version = get_contract_version(deps.storage)?;
-    if version.contract != CONTRACT_NAME {
-        return Err(ContractError::CannotMigrate {
-            previous_contract: version.contract,
-        });
+    if cfg.is_native_bonding {
+        return Err(ContractError::NotAllowedBondingType {  })
     }
-    Ok(Response::default())
-}
 
+    // On lp bonding, the platform fee and tx fee is already stolen from swap contract
+    let now = env.block.time.seconds();
+    let discount = pricing::effective_discount(&cfg, cfg.discount, pricing::current_debt(deps.storage, &cfg, now)?);
+    let receiving_amount = amount * Uint128::from(THOUSAND) / Uint128::from(THOUSAND - discount);
+    pricing::add_debt(deps.storage, &cfg, now, receiving_amount)?;
+    let bond_id = save_bond(deps.storage, address.clone(), None, None, receiving_amount, env.block.time.seconds(), cfg.term)?;
+    // what was bonded is the pool's LP token, not the Fury the deposit is worth
+    let pool_info: PoolInfoResponse = deps.querier.query_wasm_smart(&cfg.pool_address, &WasmswapQueryMsg::Info {})?;
+    let lp_token = Denom::Cw20(Addr::unchecked(pool_info.lp_token_address));
+    stats::record_bond(deps.storage, None, &lp_token, lp_amount, Uint128::zero(), receiving_amount)?;
+    // the pool already converted the LP deposit into its Fury worth, price and fee are not known here
+    bond_history().save(deps.storage, bond_id, &BondHistoryEntry {
+        discount,
+        payout: receiving_amount,
+        ..BondHistoryEntry::new(bond_id, &address, None, BondKind::LpBond {}, lp_token, lp_amount, now)
+    })?;
+
+    return Ok(Response::new()
+        .add_messages(mint_bond_nft(deps.storage, bond_id, &address)?)
+        .add_attributes(vec![
+            attr("action", "lp_bond"),
+            attr("bond_id", bond_id.to_string()),
+            attr("bond_fury_amount", amount),
+            attr("lp_amount", lp_amount),
+            attr("discount", discount.to_string()),
+            attr("receiving_amount", receiving_amount),
+            attr("address", address),
+        ]));
+}
+
+// Bonds `amount` of the market asset for `owner`, priced through the market pool
+fn execute_market_bond(
+    mut deps: DepsMut,
+    env: Env,
+    owner: Addr,
+    mut market: Market,
+    amount: Uint128,
+    min_payout: Option<Uint128>,
+    referrer: Option<Addr>
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let now = env.block.time.seconds();
+
+    if !market.is_open(now) {
+        return Err(ContractError::MarketClosed { market_id: market.id })
+    }
+
+    let quote = quote_bond(deps.as_ref(), &env, Some(&market), None, amount)?;
+    check_min_payout(min_payout, quote.payout)?;
+
+    let remaining = market.capacity - market.sold;
+    if quote.payout > remaining {
+        return Err(ContractError::MarketCapacityExceeded { remaining })
+    }
+    limits::apply_market_limits(deps.storage, &market, &owner, now, quote.payout)?;
+    market.sold += quote.payout;
+    MARKETS.save(deps.storage, market.id, &market)?;
+    pricing::add_debt(deps.storage, &cfg, now, quote.payout)?;
+
+    let bond_id = save_bond(deps.storage, owner.clone(), Some(market.id), None, quote.payout, now, market.vesting_term)?;
+    // the referral reward is paid on top and not counted against the market capacity
+    let (referral_reward, referral_msgs) = save_referral_bond(deps.branch(), &cfg, referrer, &owner, Some(market.id), &market.asset, &quote, now, market.vesting_term)?;
+    // proceeds, LP tokens included, are held by the treasury as protocol owned liquidity
+    let mut messages = proceeds_messages(deps.branch(), &cfg, &market.asset, amount)?;
+    stats::record_bond(deps.storage, Some(market.id), &market.asset, quote.bond_amount, quote.fee_amount, quote.payout)?;
+    save_bond_history(deps.storage, bond_id, &owner, Some(market.id), market.asset.clone(), amount, &quote, now)?;
+    messages.extend(mint_bond_nft(deps.storage, bond_id, &owner)?);
+    messages.extend(referral_msgs);
+
+    return Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "bond"),
+            attr("market_id", market.id.to_string()),
+            attr("bond_id", bond_id.to_string()),
+            attr("bond_amount", quote.bond_amount),
+            attr("discount", quote.discount.to_string()),
+            attr("receiving_amount", quote.payout),
+            attr("referral_reward", referral_reward),
+            attr("address", owner),
+        ]));
+}
+
+// Sends bond proceeds to the treasury. The pol_ratio share of usdc proceeds is paired with free Fury
+// and added to the pool instead, falling back to the treasury when free inventory can't match it.
+fn proceeds_messages(
+    deps: DepsMut,
+    cfg: &Config,
+    asset: &Denom,
+    amount: Uint128
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut messages:Vec<CosmosMsg> = vec![];
+    let pol_ratio = POL_RATIO.may_load(deps.storage)?.unwrap_or_default();
+    if pol_ratio == 0 || *asset != Denom::Native(cfg.usdc_denom.clone()) {
+        messages.push(util::transfer_token_message(asset.clone(), amount, cfg.treasury_address.clone())?);
+        return Ok(messages)
+    }
+
+    let info: WasmswapInfoResponse = deps
+        .querier
+        .query_wasm_smart(&cfg.pool_address, &WasmswapQueryMsg::Info {})?;
+    let pol_usdc = amount * Uint128::from(pol_ratio) / Uint128::from(THOUSAND);
+    let mut inventory = INVENTORY.may_load(deps.storage)?.unwrap_or_default();
+    if pol_usdc.is_zero() || info.lp_token_supply.is_zero() || info.token1_reserve.is_zero() {
+        messages.push(util::transfer_token_message(asset.clone(), amount, cfg.treasury_address.clone())?);
+        return Ok(messages)
+    }
+    // the pool rounds the Fury it takes up by one
+    let pol_fury = pol_usdc.multiply_ratio(info.token2_reserve, info.token1_reserve) + Uint128::one();
+    if pol_fury > inventory.free {
+        messages.push(util::transfer_token_message(asset.clone(), amount, cfg.treasury_address.clone())?);
+        return Ok(messages)
+    }
+    let liquidity = pol_usdc.multiply_ratio(info.lp_token_supply, info.token1_reserve);
+
+    inventory.free -= pol_fury;
+    INVENTORY.save(deps.storage, &inventory)?;
+    let mut pol = POL_STATS.may_load(deps.storage)?.unwrap_or_default();
+    pol.total_usdc += pol_usdc;
+    pol.total_fury += pol_fury;
+    pol.total_lp += liquidity;
+    POL_STATS.save(deps.storage, &pol)?;
+
+    if amount > pol_usdc {
+        messages.push(util::transfer_token_message(asset.clone(), amount - pol_usdc, cfg.treasury_address.clone())?);
+    }
+    messages.push(WasmMsg::Execute {
+        contract_addr: cfg.fury_token_address.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+            spender: cfg.pool_address.to_string(),
+            amount: pol_fury,
+            expires: None,
+        })?,
+        funds: vec![],
+    }.into());
+    messages.push(WasmMsg::Execute {
+        contract_addr: cfg.pool_address.to_string(),
+        msg: to_binary(&PoolExecuteMsg::AddLiquidity {
+            token1_amount: pol_usdc,
+            min_liquidity: liquidity,
+            max_token2: pol_fury,
+            fee_amount: Uint128::zero(),
+            expiration: None,
+        })?,
+        funds: vec![Coin { denom: cfg.usdc_denom.clone(), amount: pol_usdc }],
+    }.into());
+    Ok(messages)
+}
+
+fn save_bond(
+    storage: &mut dyn Storage,
+    owner: Addr,
+    market_id: Option<u64>,
+    tier: Option<u32>,
+    amount: Uint128,
+    start_time: u64,
+    vesting_term: u64
+) -> Result<u64, ContractError> {
+    // a bond can only be sold if free inventory covers its payout
+    let mut inventory = INVENTORY.may_load(storage)?.unwrap_or_default();
+    if inventory.free < amount {
+        return Err(ContractError::InsufficientFury {})
+    }
+    inventory.free -= amount;
+    inventory.committed += amount;
+    INVENTORY.save(storage, &inventory)?;
+
+    let id = next_bond_id(storage)?;
+    bonding().save(storage, id, &BondingRecord {
+        id,
+        owner,
+        market_id,
+        tier,
+        amount,
+        claimed: Uint128::zero(),
+        start_time,
+        timestamp: start_time + vesting_term
+    })?;
+    Ok(id)
+}
+
+// Pays the referrer its share of the discount as an extra bond vesting over the same term
+fn save_referral_bond(
+    deps: DepsMut,
+    cfg: &Config,
+    referrer: Option<Addr>,
+    owner: &Addr,
+    market_id: Option<u64>,
+    denom: &Denom,
+    quote: &BondQuoteResponse,
+    now: u64,
+    vesting_term: u64
+) -> Result<(Uint128, Vec<CosmosMsg>), ContractError> {
+    let referrer = match referrer {
+        Some(referrer) => deps.api.addr_validate(referrer.as_str())?,
+        None => return Ok((Uint128::zero(), vec![]))
+    };
+    if referrer == *owner {
+        return Err(ContractError::SelfReferral {})
+    }
+
+    let referral_share = REFERRAL_SHARE.may_load(deps.storage)?.unwrap_or_default();
+    let discount_amount = quote.payout * Uint128::from(quote.discount) / Uint128::from(THOUSAND);
+    let reward = discount_amount * Uint128::from(referral_share) / Uint128::from(THOUSAND);
+    if reward.is_zero() {
+        return Ok((Uint128::zero(), vec![]))
+    }
+
+    pricing::add_debt(deps.storage, cfg, now, reward)?;
+    let bond_id = save_bond(deps.storage, referrer.clone(), market_id, quote.tier, reward, now, vesting_term)?;
+    stats::record_bond(deps.storage, market_id, denom, Uint128::zero(), Uint128::zero(), reward)?;
+    bond_history().save(deps.storage, bond_id, &BondHistoryEntry {
+        tier: quote.tier,
+        fee: Some(Uint128::zero()),
+        price: Some(quote.market_price),
+        discount: quote.discount,
+        payout: reward,
+        ..BondHistoryEntry::new(bond_id, &referrer, market_id, BondKind::Referral {}, denom.clone(), Uint128::zero(), now)
+    })?;
+
+    let mut referral = REFERRALS.may_load(deps.storage, &referrer)?.unwrap_or_default();
+    referral.referred_bonds += 1;
+    referral.total_rewards += reward;
+    REFERRALS.save(deps.storage, &referrer, &referral)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    messages.extend(mint_bond_nft(deps.storage, bond_id, &referrer)?);
+    Ok((reward, messages))
+}
+
+fn save_bond_history(
+    storage: &mut dyn Storage,
+    bond_id: u64,
+    owner: &Addr,
+    market_id: Option<u64>,
+    denom: Denom,
+    amount: Uint128,
+    quote: &BondQuoteResponse,
+    now: u64
+) -> StdResult<()> {
+    bond_history().save(storage, bond_id, &BondHistoryEntry {
+        tier: quote.tier,
+        fee: Some(quote.fee_amount),
+        price: Some(quote.market_price),
+        discount: quote.discount,
+        payout: quote.payout,
+        ..BondHistoryEntry::new(bond_id, owner, market_id, BondKind::Bond {}, denom, amount, now)
+    })
+}
+
+// Mints the token representing a new bond when the NFT collection is set up
+fn mint_bond_nft(
+    storage: &dyn Storage,
+    bond_id: u64,
+    owner: &Addr
+) -> StdResult<Option<CosmosMsg>> {
+    let bond_nft = match BOND_NFT.may_load(storage)? {
+        Some(bond_nft) => bond_nft,
+        None => return Ok(None)
+    };
+    Ok(Some(WasmMsg::Execute {
+        contract_addr: bond_nft.address.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::<Extension>::Mint(MintMsg::<Extension> {
+            token_id: bond_id.to_string(),
+            owner: owner.to_string(),
+            token_uri: None,
+            extension: None,
+        }))?,
+        funds: vec![],
+    }.into()))
+}
+
+// Moves the record to whoever holds its NFT now, keeping the owner index in line with transfers
+fn sync_bond_owner(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    record: &mut BondingRecord
+) -> StdResult<()> {
+    let bond_nft = match BOND_NFT.may_load(storage)? {
+        Some(bond_nft) if bond_nft.has_token(record.id) => bond_nft,
+        _ => return Ok(())
+    };
+    let res: OwnerOfResponse = querier.query_wasm_smart(&bond_nft.address, &Cw721QueryMsg::OwnerOf {
+        token_id: record.id.to_string(),
+        include_expired: None,
+    })?;
+    let holder = Addr::unchecked(res.owner);
+    if holder != record.owner {
+        record.owner = holder;
+        bonding().save(storage, record.id, record)?;
+    }
+    Ok(())
+}
+
+pub fn execute_create_bond_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    code_id: u64,
+    name: String,
+    symbol: String
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if BOND_NFT.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::BondNftAlreadyCreated {})
+    }
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let instantiate_msg = WasmMsg::Instantiate {
+        code_id,
+        funds: vec![],
+        admin: Some(cfg.owner.to_string()),
+        label: format!("{} bond positions", name),
+        msg: to_binary(&Cw721InstantiateMsg {
+            name,
+            symbol,
+            minter: env.contract.address.to_string(),
+        })?,
+    };
+
+    return Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(instantiate_msg, INSTANTIATE_BOND_NFT_REPLY_ID))
+        .add_attributes(vec![
+            attr("action", "create_bond_nft"),
+            attr("code_id", code_id.to_string()),
+        ]));
+}
+
+pub fn execute_sync_bond_owner(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    bond_id: u64
+) -> Result<Response, ContractError> {
+    let mut record = bonding().may_load(deps.storage, bond_id)?.ok_or(ContractError::InvalidInput {})?;
+    sync_bond_owner(deps.storage, &deps.querier, &mut record)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "sync_bond_owner"),
+            attr("bond_id", bond_id.to_string()),
+            attr("owner", record.owner),
+        ]));
+}
+
+pub fn execute_claim(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bond_id: u64,
+    stake_on_claim: bool
+) -> Result<Response, ContractError> {
+
+    check_enabled(deps.storage)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let mut record = bonding().may_load(deps.storage, bond_id)?.ok_or(ContractError::InvalidInput {})?;
+    sync_bond_owner(deps.storage, &deps.querier, &mut record)?;
+    if record.owner != info.sender {
+        return Err(ContractError::Unauthorized {})
+    }
+
+    let claim_amount = record.claimable(env.block.time.seconds());
+    if claim_amount.is_zero() {
+        return Err(ContractError::StillInBonding {})
+    }
+
+    let (messages, fee) = claim_messages(deps.as_ref(), &env, &cfg, info.funds, info.sender.clone(), claim_amount, stake_on_claim)?;
+    apply_claim(deps.storage, &mut record, claim_amount, Uint128::zero(), env.block.time.seconds())?;
+    stats::record_fee(deps.storage, &Denom::Native(cfg.usdc_denom.clone()), fee)?;
+
+    return Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "claim"),
+            attr("bond_id", bond_id.to_string()),
+            attr("receiving_amount", claim_amount),
+            attr("staked", stake_on_claim.to_string()),
+            attr("claimed", record.claimed),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+pub fn execute_update_staking(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Option<Addr>
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    match address.clone() {
+        Some(address) => STAKING.save(deps.storage, &deps.api.addr_validate(address.as_str())?)?,
+        None => STAKING.remove(deps.storage),
+    }
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_staking"),
+            attr("address", address.map_or(String::from("none"), |a| a.to_string())),
+        ]));
+}
+
+pub fn execute_update_early_unbond(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_penalty: u64,
+    destination: PenaltyDestination
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if max_penalty > THOUSAND {
+        return Err(ContractError::InvalidInput {})
+    }
+    EARLY_UNBOND.save(deps.storage, &EarlyUnbondConfig {
+        max_penalty,
+        destination
+    })?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_early_unbond"),
+            attr("max_penalty", max_penalty.to_string()),
+        ]));
+}
+
+pub fn execute_early_unbond(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bond_id: u64
+) -> Result<Response, ContractError> {
+
+    check_enabled(deps.storage)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let early_unbond = EARLY_UNBOND.may_load(deps.storage)?.ok_or(ContractError::EarlyUnbondDisabled {})?;
+
+    let mut record = bonding().may_load(deps.storage, bond_id)?.ok_or(ContractError::InvalidInput {})?;
+    sync_bond_owner(deps.storage, &deps.querier, &mut record)?;
+    if record.owner != info.sender {
+        return Err(ContractError::Unauthorized {})
+    }
+
+    let (payout, penalty) = pricing::early_unbond_amounts(&record, &early_unbond, env.block.time.seconds());
+
+    // a bond unbonded at the full penalty rate pays nothing, so there is no transfer and no fee
+    let (mut messages, fee) = if payout.is_zero() {
+        (vec![], Uint128::zero())
+    } else {
+        claim_messages(deps.as_ref(), &env, &cfg, info.funds, info.sender.clone(), payout, false)?
+    };
+    if !penalty.is_zero() {
+        messages.push(match early_unbond.destination {
+            PenaltyDestination::Treasury {} => util::transfer_token_message(Denom::Cw20(cfg.fury_token_address.clone()), penalty, cfg.treasury_address.clone())?,
+            PenaltyDestination::Burn {} => WasmMsg::Execute {
+                contract_addr: cfg.fury_token_address.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn { amount: penalty })?,
+                funds: vec![],
+            }.into(),
+        });
+    }
+    apply_claim(deps.storage, &mut record, payout, penalty, env.block.time.seconds())?;
+    stats::record_fee(deps.storage, &Denom::Native(cfg.usdc_denom.clone()), fee)?;
+
+    return Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "early_unbond"),
+            attr("bond_id", bond_id.to_string()),
+            attr("receiving_amount", payout),
+            attr("penalty", penalty),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+pub fn execute_claim_all(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    stake_on_claim: bool
+) -> Result<Response, ContractError> {
+
+    check_enabled(deps.storage)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let (claim_amount, bond_count, pruned) = claim_owner_bonds(deps.branch(), &env, &info.sender, start_after, limit, false)?;
+
+    // one payout and one combined fee for every bond claimed
+    let (messages, fee) = claim_messages(deps.as_ref(), &env, &cfg, info.funds, info.sender.clone(), claim_amount, stake_on_claim)?;
+    stats::record_fee(deps.storage, &Denom::Native(cfg.usdc_denom.clone()), fee)?;
+
+    return Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "claim_all"),
+            attr("staked", stake_on_claim.to_string()),
+            attr("bond_count", bond_count.to_string()),
+            attr("pruned_count", pruned.to_string()),
+            attr("receiving_amount", claim_amount),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+// Claims the claimable bonds among one page of owner's bonds, returning the amount, bonds claimed and bonds pruned.
+// With matured_only set, bonds still vesting are left alone.
+fn claim_owner_bonds(
+    deps: DepsMut,
+    env: &Env,
+    owner: &Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    matured_only: bool
+) -> Result<(Uint128, u64, u64), ContractError> {
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // owners come from the NFT collection, saving the claimed record moves the index along
+    let records = owner_bonds(deps.as_ref(), owner, start_after, limit)?;
+
+    let mut claim_amount = Uint128::zero();
+    let mut pruned = 0u64;
+    let mut bond_count = 0u64;
+    for mut record in records {
+        let amount = record.claimable(now);
+        if amount.is_zero() || (matured_only && record.timestamp > now) {
+            continue;
+        }
+        claim_amount += amount;
+        bond_count += 1;
+        apply_claim(deps.storage, &mut record, amount, Uint128::zero(), now)?;
+        if record.is_fully_claimed() {
+            pruned += 1;
+        }
+    }
+
+    if claim_amount.is_zero() {
+        return Err(ContractError::StillInBonding {})
+    }
+    Ok((claim_amount, bond_count, pruned))
+}
+
+pub fn execute_update_claim_operator(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    operator: Addr,
+    approved: bool
+) -> Result<Response, ContractError> {
+    let operator = deps.api.addr_validate(operator.as_str())?;
+    if approved {
+        CLAIM_OPERATORS.save(deps.storage, (&info.sender, &operator), &true)?;
+    } else {
+        CLAIM_OPERATORS.remove(deps.storage, (&info.sender, &operator));
+    }
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_claim_operator"),
+            attr("operator", operator),
+            attr("approved", approved.to_string()),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+pub fn execute_update_claim_settings(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    keeper_claims: bool,
+    recipient: Option<Addr>
+) -> Result<Response, ContractError> {
+    let recipient = recipient.map(|r| deps.api.addr_validate(r.as_str())).transpose()?;
+    CLAIM_SETTINGS.save(deps.storage, &info.sender, &ClaimSettings {
+        keeper_claims,
+        recipient: recipient.clone()
+    })?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_claim_settings"),
+            attr("keeper_claims", keeper_claims.to_string()),
+            attr("recipient", recipient.unwrap_or(info.sender.clone())),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+pub fn execute_update_pol_ratio(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    pol_ratio: u64
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if pol_ratio > THOUSAND {
+        return Err(ContractError::InvalidInput {})
+    }
+    POL_RATIO.save(deps.storage, &pol_ratio)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_pol_ratio"),
+            attr("pol_ratio", pol_ratio.to_string()),
+        ]));
+}
+
+pub fn execute_update_referral_share(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    referral_share: u64
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if referral_share > THOUSAND {
+        return Err(ContractError::InvalidInput {})
+    }
+    REFERRAL_SHARE.save(deps.storage, &referral_share)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_referral_share"),
+            attr("referral_share", referral_share.to_string()),
+        ]));
+}
+
+pub fn execute_update_tiers(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tiers: Vec<BondTier>
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    for tier in tiers.iter() {
+        if tier.term == 0 || exceeds_thousand(&[tier.discount, cfg.tx_fee, cfg.platform_fee]) {
+            return Err(ContractError::InvalidInput {})
+        }
+    }
+    TIERS.save(deps.storage, &tiers)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_tiers"),
+            attr("tier_count", tiers.len().to_string()),
+        ]));
+}
+
+pub fn execute_update_keeper_tip(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tip: Uint128
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    KEEPER_TIP.save(deps.storage, &tip)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_keeper_tip"),
+            attr("tip", tip),
+        ]));
+}
+
+// Claims bonds of owner for an approved operator, or matured bonds for any keeper when the owner opted in.
+// The caller sends the usdc fee, keepers get the tip out of the payout in return. Keepers only settle
+// fully vested bonds, so repeated keeper claims cannot skim the tip off a vesting bond every block.
+pub fn execute_claim_for(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>
+) -> Result<Response, ContractError> {
+
+    check_enabled(deps.storage)?;
+
+    let settings = CLAIM_SETTINGS.may_load(deps.storage, &owner)?.unwrap_or_default();
+    let is_operator = CLAIM_OPERATORS.has(deps.storage, (&owner, &info.sender));
+    if !is_operator && !settings.keeper_claims {
+        return Err(ContractError::Unauthorized {})
+    }
+    let tip = if is_operator {
+        Uint128::zero()
+    } else {
+        KEEPER_TIP.may_load(deps.storage)?.unwrap_or_default()
+    };
+    let recipient = settings.recipient.unwrap_or(owner.clone());
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let (claim_amount, bond_count, pruned) = claim_owner_bonds(deps.branch(), &env, &owner, start_after, limit, !is_operator)?;
+    if claim_amount <= tip {
+        return Err(ContractError::ClaimBelowTip { tip })
+    }
+
+    let (mut messages, fee) = claim_messages(deps.as_ref(), &env, &cfg, info.funds, recipient.clone(), claim_amount - tip, false)?;
+    if !tip.is_zero() {
+        messages.push(util::transfer_token_message(Denom::Cw20(cfg.fury_token_address.clone()), tip, info.sender.clone())?);
+    }
+    stats::record_fee(deps.storage, &Denom::Native(cfg.usdc_denom.clone()), fee)?;
+
+    return Ok(Response::new()
+        .add_messages(messages)
+        .add_attributes(vec![
+            attr("action", "claim_for"),
+            attr("owner", owner),
+            attr("recipient", recipient),
+            attr("bond_count", bond_count.to_string()),
+            attr("pruned_count", pruned.to_string()),
+            attr("receiving_amount", claim_amount - tip),
+            attr("tip", tip),
+            attr("address", info.sender.clone()),
+        ]));
+}
+
+// Checks the usdc fee sent along for paying out claim_amount Fury and builds the transfers
+fn claim_messages(
+    deps: Deps,
+    env: &Env,
+    cfg: &Config,
+    funds: Vec<Coin>,
+    recipient: Addr,
+    claim_amount: Uint128,
+    stake: bool
+) -> Result<(Vec<CosmosMsg>, Uint128), ContractError> {
+    // no usdc sent counts as a zero fee
+    let usdc_amount = funds
+        .iter()
+        .find(|coin| coin.denom == cfg.usdc_denom)
+        .map(|coin| coin.amount)
+        .unwrap_or_default();
+
+    //calculate tx fee on the part being paid out, the price is only needed when there is a fee
+    if cfg.platform_fee + cfg.tx_fee > 0 {
+        let fury_price = oracle::query_fury_price(deps, env, cfg)?;
+        let claim_value = claim_amount * fury_price;
+
+        if usdc_amount < claim_value * Uint128::from(cfg.platform_fee + cfg.tx_fee) / Uint128::from(THOUSAND) {
+            return Err(ContractError::InsufficientFee { })
+        }
+    }
+
+    let mut messages:Vec<CosmosMsg> = vec![];
+    if stake {
+        // the payout goes straight into staking, credited to the recipient by the hook
+        let staking = STAKING.may_load(deps.storage)?.ok_or(ContractError::StakingNotConfigured {})?;
+        messages.push(WasmMsg::Execute {
+            contract_addr: cfg.fury_token_address.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: staking.to_string(),
+                amount: claim_amount,
+                msg: to_binary(&StakingHookMsg::Stake { address: recipient.to_string() })?,
+            })?,
+            funds: vec![],
+        }.into());
+    } else {
+        messages.push(util::transfer_token_message(Denom::Cw20(cfg.fury_token_address.clone()), claim_amount, recipient)?);
+    }
+    if !usdc_amount.is_zero() {
+        messages.push(util::transfer_token_message(Denom::Native(cfg.usdc_denom.clone()), usdc_amount, cfg.treasury_address.clone())?);
+    }
+    Ok((messages, usdc_amount))
+}
+
+// Marks payout and penalty as claimed and prunes the record once everything is paid out
+fn apply_claim(
+    storage: &mut dyn Storage,
+    record: &mut BondingRecord,
+    payout: Uint128,
+    penalty: Uint128,
+    now: u64
+) -> StdResult<()> {
+    let amount = payout + penalty;
+    INVENTORY.update(storage, |mut inventory| -> StdResult<_> {
+        inventory.committed = inventory.committed.checked_sub(amount)?;
+        Ok(inventory)
+    })?;
+
+    record.claimed += amount;
+    if record.is_fully_claimed() {
+        bonding().remove(storage, record.id)?;
+    } else {
+        bonding().save(storage, record.id, record)?;
+    }
+    // bonds sold before the ledger existed have no entry
+    if let Some(mut entry) = bond_history().may_load(storage, record.id)? {
+        entry.claimed += payout;
+        entry.penalty += penalty;
+        entry.last_claimed_at = Some(now);
+        if record.is_fully_claimed() {
+            entry.closed_at = Some(now);
+        }
+        bond_history().save(storage, record.id, &entry)?;
+    }
+    stats::record_claim(storage, record, payout, penalty)
+}
+
+pub fn execute_update_withdraw_delay(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    delay: u64
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if delay < MIN_WITHDRAW_DELAY {
+        return Err(ContractError::WithdrawDelayTooShort { min: MIN_WITHDRAW_DELAY })
+    }
+    WITHDRAW_DELAY.save(deps.storage, &delay)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "update_withdraw_delay"),
+            attr("delay", delay.to_string()),
+        ]));
+}
+
+pub fn execute_propose_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: Denom,
+    amount: Uint128,
+    recipient: Addr
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if amount.is_zero() {
+        return Err(ContractError::InvalidInput {})
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    check_withdrawable(deps.storage, &cfg, &denom, amount)?;
+
+    // a new proposal replaces the pending one and restarts the timelock
+    let delay = WITHDRAW_DELAY.may_load(deps.storage)?.unwrap_or(DEFAULT_WITHDRAW_DELAY);
+    let proposal = WithdrawProposal {
+        denom,
+        amount,
+        recipient: deps.api.addr_validate(recipient.as_str())?,
+        executable_at: env.block.time.seconds() + delay,
+    };
+    PENDING_WITHDRAW.save(deps.storage, &proposal)?;
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "propose_withdraw"),
+            attr("amount", amount),
+            attr("recipient", proposal.recipient),
+            attr("executable_at", proposal.executable_at.to_string()),
+        ]));
+}
+
+pub fn execute_execute_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    let proposal = PENDING_WITHDRAW.may_load(deps.storage)?.ok_or(ContractError::NoPendingWithdraw {})?;
+    if env.block.time.seconds() < proposal.executable_at {
+        return Err(ContractError::WithdrawTimelocked { executable_at: proposal.executable_at })
+    }
+
+    // bonds sold during the timelock may have used up the free Fury
+    let cfg = CONFIG.load(deps.storage)?;
+    check_withdrawable(deps.storage, &cfg, &proposal.denom, proposal.amount)?;
+    if proposal.denom == Denom::Cw20(cfg.fury_token_address.clone()) {
+        INVENTORY.update(deps.storage, |mut inventory| -> StdResult<_> {
+            inventory.free -= proposal.amount;
+            Ok(inventory)
+        })?;
+    }
+    PENDING_WITHDRAW.remove(deps.storage);
+
+    return Ok(Response::new()
+        .add_message(util::transfer_token_message(proposal.denom, proposal.amount, proposal.recipient.clone())?)
+        .add_attributes(vec![
+            attr("action", "execute_withdraw"),
+            attr("amount", proposal.amount),
+            attr("recipient", proposal.recipient),
+        ]));
+}
+
+pub fn execute_cancel_withdraw(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo
+) -> Result<Response, ContractError> {
+    check_owner(deps.storage, info.sender.clone())?;
+
+    if PENDING_WITHDRAW.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoPendingWithdraw {})
+    }
+    PENDING_WITHDRAW.remove(deps.storage);
+
+    return Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "cancel_withdraw"),
+        ]));
+}
+
+// Fury can only be withdrawn from the free inventory, never what bonds are owed
+fn check_withdrawable(
+    storage: &dyn Storage,
+    cfg: &Config,
+    denom: &Denom,
+    amount: Uint128
+) -> Result<(), ContractError> {
+    if *denom == Denom::Cw20(cfg.fury_token_address.clone()) {
+        let inventory = INVENTORY.may_load(storage)?.unwrap_or_default();
+        if inventory.free < amount {
+            return Err(ContractError::InsufficientFury {})
+        }
+    }
+    Ok(())
+}
+
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {}
+            => to_binary(&query_config(deps)?),
+        QueryMsg::Bond {bond_id}
+            => to_binary(&bonding().load(deps.storage, bond_id)?),
+        QueryMsg::BondState {address, start_after, limit}
+            => to_binary(&query_bond_state(deps, address, start_after, limit)?),
+        QueryMsg::AllBondState {start_after, limit}
+            => to_binary(&query_all_bond_state(deps, start_after, limit)?),
+        QueryMsg::Claimable {address, start_after, limit}
+            => to_binary(&query_claimable(deps, _env, address, start_after, limit)?),
+        QueryMsg::BondPrice {}
+            => to_binary(&query_bond_price(deps, _env)?),
+        QueryMsg::Market {market_id}
+            => to_binary(&MARKETS.load(deps.storage, market_id)?),
+        QueryMsg::Markets {start_after, limit}
+            => to_binary(&query_markets(deps, start_after, limit)?),
+        QueryMsg::Inventory {}
+            => to_binary(&query_inventory(deps)?),
+        QueryMsg::BondQuote {market_id, tier, amount}
+            => to_binary(&query_bond_quote(deps, _env, market_id, tier, amount)?),
+        QueryMsg::Tiers {}
+            => to_binary(&TiersResponse { tiers: TIERS.may_load(deps.storage)?.unwrap_or_default() }),
+        QueryMsg::EarlyUnbondPreview {bond_id}
+            => to_binary(&query_early_unbond_preview(deps, _env, bond_id)?),
+        QueryMsg::BondNft {}
+            => to_binary(&query_bond_nft(deps)?),
+        QueryMsg::Staking {}
+            => to_binary(&StakingResponse { address: STAKING.may_load(deps.storage)? }),
+        QueryMsg::Stats {}
+            => to_binary(&query_stats(deps)?),
+        QueryMsg::MarketStats {market_id}
+            => to_binary(&query_market_stats(deps, market_id)?),
+        QueryMsg::MarketLimits {market_id}
+            => to_binary(&MarketLimitsResponse { market_id, limits: MARKET_LIMITS.may_load(deps.storage, market_id)? }),
+        QueryMsg::RemainingCapacity {market_id, address}
+            => to_binary(&query_remaining_capacity(deps, _env, market_id, address)?),
+        QueryMsg::ClaimSettings {address}
+            => to_binary(&query_claim_settings(deps, address)?),
+        QueryMsg::BondHistory {address, start_time, end_time, start_after, limit}
+            => to_binary(&query_bond_history(deps, address, start_time, end_time, start_after, limit)?),
+        QueryMsg::Pol {}
+            => to_binary(&query_pol(deps)?),
+        QueryMsg::Referral {address}
+            => to_binary(&query_referral(deps, address)?),
+        QueryMsg::KeeperTip {}
+            => to_binary(&KeeperTipResponse { tip: KEEPER_TIP.may_load(deps.storage)?.unwrap_or_default() }),
+        QueryMsg::PendingWithdraw {}
+            => to_binary(&PendingWithdrawResponse {
+                delay: WITHDRAW_DELAY.may_load(deps.storage)?.unwrap_or(DEFAULT_WITHDRAW_DELAY),
+                proposal: PENDING_WITHDRAW.may_load(deps.storage)?,
+            }),
+    }
+}
+
+// History of bonds bought by address, optionally only those created within [start_time, end_time)
+pub fn query_bond_history(
+    deps: Deps,
+    address: Addr,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<BondHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let start_time = start_time.unwrap_or(0);
+    let end_time = end_time.unwrap_or(u64::MAX);
+
+    let list = bond_history()
+        .idx
+        .owner
+        .prefix(address.clone())
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, entry)| entry))
+        .filter(|item| item.as_ref().map_or(true, |entry| entry.created_at >= start_time && entry.created_at < end_time))
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(BondHistoryResponse {
+        address,
+        list
+    })
+}
+
+pub fn query_referral(deps: Deps, address: Addr) -> StdResult<ReferralResponse> {
+    let referral = REFERRALS.may_load(deps.storage, &address)?.unwrap_or_default();
+    Ok(ReferralResponse {
+        address,
+        referral_share: REFERRAL_SHARE.may_load(deps.storage)?.unwrap_or_default(),
+        referred_bonds: referral.referred_bonds,
+        total_rewards: referral.total_rewards,
+    })
+}
+
+pub fn query_pol(deps: Deps) -> StdResult<PolResponse> {
+    let pol = POL_STATS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PolResponse {
+        pol_ratio: POL_RATIO.may_load(deps.storage)?.unwrap_or_default(),
+        total_usdc: pol.total_usdc,
+        total_fury: pol.total_fury,
+        total_lp: pol.total_lp,
+    })
+}
+
+pub fn query_claim_settings(deps: Deps, address: Addr) -> StdResult<ClaimSettingsResponse> {
+    let settings = CLAIM_SETTINGS.may_load(deps.storage, &address)?.unwrap_or_default();
+    let operators = CLAIM_OPERATORS
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(MAX_LIMIT as usize)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(ClaimSettingsResponse {
+        address,
+        keeper_claims: settings.keeper_claims,
+        recipient: settings.recipient,
+        operators,
+    })
+}
+
+pub fn query_remaining_capacity(deps: Deps, env: Env, market_id: u64, address: Option<Addr>) -> StdResult<RemainingCapacityResponse> {
+    let market = MARKETS.load(deps.storage, market_id)?;
+    limits::remaining_capacity(deps.storage, &market, address, env.block.time.seconds())
+        .map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+pub fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
+    let stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    let inventory = INVENTORY.may_load(deps.storage)?.unwrap_or_default();
+    let denoms = DENOM_STATS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, denom_stats)| denom_stats))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(StatsResponse {
+        total_payout: stats.total_payout,
+        fury_owed: inventory.committed,
+        total_claimed: stats.total_claimed,
+        total_penalty: stats.total_penalty,
+        active_bonds: stats.active_bonds,
+        denoms,
+    })
+}
+
+pub fn query_market_stats(deps: Deps, market_id: u64) -> StdResult<MarketStatsResponse> {
+    let market = MARKETS.load(deps.storage, market_id)?;
+    let stats = MARKET_STATS.may_load(deps.storage, market_id)?.unwrap_or_default();
+    Ok(MarketStatsResponse {
+        market_id,
+        asset: market.asset,
+        fury_owed: stats.payout - stats.claimed - stats.penalty,
+        stats,
+    })
+}
+
+pub fn query_bond_nft(deps: Deps) -> StdResult<BondNftResponse> {
+    let bond_nft = BOND_NFT.may_load(deps.storage)?;
+    Ok(BondNftResponse {
+        address: bond_nft.clone().map(|bond_nft| bond_nft.address),
+        first_bond_id: bond_nft.map(|bond_nft| bond_nft.first_bond_id),
+    })
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: cfg.owner,
+        pool_address: cfg.pool_address,
+        treasury_address: cfg.treasury_address,
+        fury_token_address: cfg.fury_token_address,
+        term: cfg.term,
+        discount: cfg.discount,
+        usdc_denom: cfg.usdc_denom,
+        is_native_bonding: cfg.is_native_bonding,
+        tx_fee: cfg.tx_fee,
+        platform_fee: cfg.platform_fee,
+        enabled: cfg.enabled,
+        price_source: cfg.price_source,
+        max_price_age: cfg.max_price_age,
+        max_price_deviation: cfg.max_price_deviation,
+        control_variable: cfg.control_variable,
+        min_price: cfg.min_price,
+        max_debt: cfg.max_debt,
+    })
+}
+
+pub fn query_bond_price(deps: Deps, env: Env) -> StdResult<BondPriceResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let current_debt = pricing::current_debt(deps.storage, &cfg, env.block.time.seconds())?;
+    let discount = pricing::effective_discount(&cfg, cfg.discount, current_debt);
+    let market_price = oracle::query_spot_price(deps, &cfg)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(BondPriceResponse {
+        discount,
+        market_price,
+        bond_price: pricing::bond_price(&cfg, market_price, discount),
+        current_debt,
+        max_debt: cfg.max_debt,
+    })
+}
+
+// Claimable amounts among one page of the bonds address holds, paged like BondState
+pub fn query_claimable(
+    deps: Deps,
+    env: Env,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ClaimableResponse> {
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut amount = Uint128::zero();
+    let mut list: Vec<ClaimableBond> = vec![];
+
+    for record in owner_bonds(deps, &address, start_after, limit)? {
+        let bond_id = record.id;
+        let claimable = record.claimable(now);
+        if claimable.is_zero() {
+            continue;
+        }
+        amount += claimable;
+        list.push(ClaimableBond {
+            bond_id,
+            amount: claimable
+        });
+    }
+
+    Ok(ClaimableResponse {
+        address,
+        amount,
+        list
+    })
+}
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+pub fn query_bond_state(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<BondStateResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let list = owner_bonds(deps, &address, start_after, limit)?;
+
+    Ok(BondStateResponse {
+        address,
+        list
+    })
+}
+
+// One page of the bonds owner holds. Bonds without a token follow the owner index in id order,
+// then come the tokens owner holds in the NFT collection in token id order, so transfers show
+// up at once. Pass the last bond id returned as start_after for the next page.
+fn owner_bonds(
+    deps: Deps,
+    owner: &Addr,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<Vec<BondingRecord>> {
+    let bond_nft = BOND_NFT.may_load(deps.storage)?;
+    let mut records: Vec<BondingRecord> = vec![];
+
+    let in_tokens = match (&bond_nft, start_after) {
+        (Some(bond_nft), Some(id)) => bond_nft.has_token(id),
+        _ => false,
+    };
+    if !in_tokens {
+        let start = start_after.map(Bound::exclusive);
+        let end = bond_nft.as_ref().map(|bond_nft| Bound::exclusive(bond_nft.first_bond_id));
+        records = bonding()
+            .idx
+            .owner
+            .prefix(owner.clone())
+            .range(deps.storage, start, end, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, record)| record))
+            .collect::<StdResult<_>>()?;
+    }
+
+    let bond_nft = match bond_nft {
+        Some(bond_nft) => bond_nft,
+        None => return Ok(records),
+    };
+    let mut token_start = start_after.filter(|id| bond_nft.has_token(*id)).map(|id| id.to_string());
+    // paid out bonds keep their token, read on past them until the page is full
+    while records.len() < limit {
+        let res: TokensResponse = deps.querier.query_wasm_smart(&bond_nft.address, &Cw721QueryMsg::Tokens {
+            owner: owner.to_string(),
+            start_after: token_start,
+            limit: Some(limit as u32),
+        })?;
+        token_start = res.tokens.last().cloned();
+        if token_start.is_none() {
+            break;
+        }
+        for token_id in res.tokens {
+            let bond_id: u64 = token_id.parse().map_err(|_| StdError::generic_err("invalid bond token id"))?;
+            if let Some(mut record) = bonding().may_load(deps.storage, bond_id)? {
+                record.owner = owner.clone();
+                records.push(record);
+                if records.len() == limit {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(records)
+}
+
+
+pub fn query_bond_quote(
+    deps: Deps,
+    env: Env,
+    market_id: Option<u64>,
+    tier: Option<u32>,
+    amount: Uint128,
+) -> StdResult<BondQuoteResponse> {
+    let market = match market_id {
+        Some(market_id) => Some(MARKETS.load(deps.storage, market_id)?),
+        None => None,
+    };
+    quote_bond(deps, &env, market.as_ref(), tier, amount)
+        .map_err(|e| StdError::generic_err(e.to_string()))
+}
+
+pub fn query_early_unbond_preview(
+    deps: Deps,
+    env: Env,
+    bond_id: u64,
+) -> StdResult<EarlyUnbondPreviewResponse> {
+    let early_unbond = EARLY_UNBOND.may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err(ContractError::EarlyUnbondDisabled {}.to_string()))?;
+    let record = bonding().load(deps.storage, bond_id)?;
+    let (payout, penalty) = pricing::early_unbond_amounts(&record, &early_unbond, env.block.time.seconds());
+
+    Ok(EarlyUnbondPreviewResponse {
+        bond_id,
+        payout,
+        penalty,
+        destination: early_unbond.destination,
+    })
+}
+
+pub fn query_inventory(deps: Deps) -> StdResult<InventoryResponse> {
+    let inventory = INVENTORY.may_load(deps.storage)?.unwrap_or_default();
+    Ok(InventoryResponse {
+        committed: inventory.committed,
+        free: inventory.free,
+    })
+}
+
+pub fn query_markets(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MarketsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let list:StdResult<Vec<_>> = MARKETS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, market)| market))
+        .collect();
+
+    Ok(MarketsResponse { list: list? })
+}
+
+
+fn query_all_bond_state(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<AllBondStateResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let list:StdResult<Vec<_>> = bonding()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, record)| record))
+        .collect();
+
+    Ok(AllBondStateResponse { list: list? })
+}
+
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != INSTANTIATE_BOND_NFT_REPLY_ID {
+        return Err(ContractError::UnknownReplyId { id: msg.id });
+    }
+    let res = parse_reply_instantiate_data(msg)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+    let address = deps.api.addr_validate(&res.contract_address)?;
+
+    // only bonds created from now on get a token
+    let first_bond_id = BOND_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    BOND_NFT.save(deps.storage, &BondNft {
+        address: address.clone(),
+        first_bond_id
+    })?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "bond_nft_created"),
+            attr("address", address),
+            attr("first_bond_id", first_bond_id.to_string()),
+        ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: version.contract,
+        });
+    }
+
+    // configs counting lock_days keep the same term in seconds, those written before the price
+    // source existed get spot pricing with every check off
+    if CONFIG.load(deps.storage).is_err() {
+        let legacy = LEGACY_CONFIG.load(deps.storage)?;
+        let config = Config {
+            owner: legacy.owner,
+            pool_address: legacy.pool_address,
+            treasury_address: legacy.treasury_address,
+            fury_token_address: legacy.fury_token_address,
+            term: legacy.lock_days * 86400,
+            discount: legacy.discount,
+            usdc_denom: legacy.usdc_denom,
+            is_native_bonding: legacy.is_native_bonding,
+            tx_fee: legacy.tx_fee,
+            platform_fee: legacy.platform_fee,
+            enabled: legacy.enabled,
+            price_source: legacy.price_source.unwrap_or(PriceSource::PoolSpot {}),
+            max_price_age: legacy.max_price_age,
+            max_price_deviation: legacy.max_price_deviation,
+            control_variable: legacy.control_variable,
+            min_price: legacy.min_price,
+            max_debt: legacy.max_debt,
+        };
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new().add_attributes(vec![attr("action", "migrate")]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use crate::state::LegacyConfig;
+
+    #[test]
+    fn fee_sums_reject_overflow() {
+        assert!(!exceeds_thousand(&[10, 10, 979]));
+        assert!(exceeds_thousand(&[10, 10, 980]));
+        assert!(exceeds_thousand(&[u64::MAX, 2, 0]));
+    }
+
+    #[test]
+    fn migrate_legacy_config() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        let legacy = LegacyConfig {
+            owner: Addr::unchecked("owner"),
+            pool_address: Addr::unchecked("pool"),
+            treasury_address: Addr::unchecked("treasury"),
+            fury_token_address: Addr::unchecked("fury"),
+            lock_days: 5,
+            discount: 50,
+            usdc_denom: "uusdc".to_string(),
+            is_native_bonding: true,
+            tx_fee: 10,
+            platform_fee: 10,
+            enabled: true,
+            price_source: None,
+            max_price_age: 0,
+            max_price_deviation: 0,
+            control_variable: 0,
+            min_price: Decimal::zero(),
+            max_debt: None,
+        };
+        LEGACY_CONFIG.save(deps.as_mut().storage, &legacy).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(cfg.owner, legacy.owner);
+        assert_eq!(cfg.term, 5 * 86400);
+        assert_eq!(cfg.discount, 50);
+        assert_eq!(cfg.price_source, PriceSource::PoolSpot {});
+        assert_eq!(cfg.max_debt, None);
+        assert_eq!(get_contract_version(deps.as_ref().storage).unwrap().version, CONTRACT_VERSION);
+
+        // a current config is left untouched
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap(), cfg);
+    }
+}
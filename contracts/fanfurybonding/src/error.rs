@@ -1,4 +1,4 @@
-use cosmwasm_std::{StdError};
+use cosmwasm_std::{Decimal, StdError, Uint128};
 use hex::FromHexError;
 use thiserror::Error;
 
@@ -63,4 +63,82 @@ pub enum ContractError {
 
     #[error("Token type mismatch")]
     TokenTypeMismatch {},
+
+    #[error("No liquidity in pool")]
+    NoLiquidity {},
+
+    #[error("Stale price: updated at {updated_at}, max age {max_age}")]
+    StalePrice { updated_at: u64, max_age: u64 },
+
+    #[error("Price {price} deviates too far from pool price {spot}")]
+    PriceDeviation { price: Decimal, spot: Decimal },
+
+    #[error("Max debt reached: current {current}, max {max}")]
+    MaxDebtReached { current: Uint128, max: Uint128 },
+
+    #[error("Market {market_id} not found")]
+    MarketNotFound { market_id: u64 },
+
+    #[error("Market {market_id} is not open")]
+    MarketClosed { market_id: u64 },
+
+    #[error("Market capacity exceeded: remaining {remaining}")]
+    MarketCapacityExceeded { remaining: Uint128 },
+
+    #[error("Min payout error: min: {min_payout}, payout: {payout}")]
+    MinPayoutError { min_payout: Uint128, payout: Uint128 },
+
+    #[error("MsgExpirationError")]
+    MsgExpirationError {},
+
+    #[error("Early unbonding is not enabled")]
+    EarlyUnbondDisabled {},
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Bond NFT collection already created")]
+    BondNftAlreadyCreated {},
+
+    #[error("No staking contract configured")]
+    StakingNotConfigured {},
+
+    #[error("{address} is not allowlisted for market {market_id}")]
+    NotAllowlisted { market_id: u64, address: String },
+
+    #[error("Epoch {epoch} capacity exceeded: remaining {remaining}")]
+    EpochCapacityExceeded { epoch: u64, remaining: Uint128 },
+
+    #[error("Address cap for epoch {epoch} exceeded: remaining {remaining}")]
+    AddressCapExceeded { epoch: u64, remaining: Uint128 },
+
+    #[error("No withdrawal proposed")]
+    NoPendingWithdraw {},
+
+    #[error("Market pool is required for assets other than usdc")]
+    MarketPoolRequired {},
+
+    #[error("Market pool does not trade the market asset against Fury")]
+    MarketPoolMismatch {},
+
+    #[error("Fees leave no room for discount {discount}")]
+    FeesTooHigh { discount: u64 },
+
+    #[error("Withdraw delay must be at least {min} seconds")]
+    WithdrawDelayTooShort { min: u64 },
+
+    #[error("Withdrawal is timelocked until {executable_at}")]
+    WithdrawTimelocked { executable_at: u64 },
+
+    #[error("Claimable amount does not cover the keeper tip {tip}")]
+    ClaimBelowTip { tip: Uint128 },
+
+    #[error("Cannot refer yourself")]
+    SelfReferral {},
+
+    #[error("Tier {tier} not found")]
+    TierNotFound { tier: u32 },
+
+    #[error("Tier {tier} needs a bond of at least {min_amount}")]
+    TierMinAmount { tier: u32, min_amount: Uint128 },
 }
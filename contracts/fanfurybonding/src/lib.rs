@@ -1,6 +1,15 @@
+// handlers end in an explicit return and take their message fields as arguments,
+// to_binary and from_binary are kept for cosmwasm-std 1.0
+#![allow(clippy::needless_return, clippy::too_many_arguments, deprecated)]
+
 pub mod contract;
 mod error;
+pub mod limits;
 pub mod msg;
+pub mod oracle;
+pub mod pricing;
 pub mod state;
+pub mod stats;
+#[path = "../../../util/util.rs"]
 pub mod util;
 pub use crate::error::ContractError;
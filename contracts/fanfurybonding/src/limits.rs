@@ -0,0 +1,191 @@
+use cosmwasm_std::{Addr, Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::RemainingCapacityResponse;
+use crate::state::{Market, ADDRESS_EPOCH_SOLD, ALLOWLIST, EPOCH_SOLD, MARKET_LIMITS};
+
+// Checks a payout against the market limits and counts it towards the current epoch
+pub fn apply_market_limits(
+    storage: &mut dyn Storage,
+    market: &Market,
+    address: &Addr,
+    now: u64,
+    payout: Uint128,
+) -> Result<(), ContractError> {
+    let limits = match MARKET_LIMITS.may_load(storage, market.id)? {
+        Some(limits) => limits,
+        None => return Ok(()),
+    };
+
+    if limits.allowlist_enabled && !ALLOWLIST.has(storage, (market.id, address)) {
+        return Err(ContractError::NotAllowlisted {
+            market_id: market.id,
+            address: address.to_string(),
+        });
+    }
+
+    let epoch = limits.epoch(market, now);
+    let epoch_sold = EPOCH_SOLD.may_load(storage, (market.id, epoch))?.unwrap_or_default();
+    if let Some(epoch_capacity) = limits.epoch_capacity {
+        let remaining = epoch_capacity.saturating_sub(epoch_sold);
+        if payout > remaining {
+            return Err(ContractError::EpochCapacityExceeded { epoch, remaining });
+        }
+    }
+    let address_sold = ADDRESS_EPOCH_SOLD
+        .may_load(storage, (market.id, epoch, address))?
+        .unwrap_or_default();
+    if let Some(address_cap) = limits.address_cap {
+        let remaining = address_cap.saturating_sub(address_sold);
+        if payout > remaining {
+            return Err(ContractError::AddressCapExceeded { epoch, remaining });
+        }
+    }
+
+    EPOCH_SOLD.save(storage, (market.id, epoch), &(epoch_sold + payout))?;
+    ADDRESS_EPOCH_SOLD.save(storage, (market.id, epoch, address), &(address_sold + payout))?;
+    Ok(())
+}
+
+pub fn remaining_capacity(
+    storage: &dyn Storage,
+    market: &Market,
+    address: Option<Addr>,
+    now: u64,
+) -> Result<RemainingCapacityResponse, ContractError> {
+    let market_remaining = market.capacity.saturating_sub(market.sold);
+    let limits = match MARKET_LIMITS.may_load(storage, market.id)? {
+        Some(limits) => limits,
+        None => {
+            return Ok(RemainingCapacityResponse {
+                market_id: market.id,
+                epoch: 0,
+                epoch_end: market.end_time,
+                market_remaining,
+                epoch_remaining: None,
+                address_remaining: None,
+                allowlisted: true,
+            })
+        }
+    };
+
+    let epoch = limits.epoch(market, now);
+    let epoch_end = if limits.epoch_length == 0 {
+        market.end_time
+    } else {
+        (market.start_time + (epoch + 1) * limits.epoch_length).min(market.end_time)
+    };
+    let epoch_remaining = match limits.epoch_capacity {
+        Some(epoch_capacity) => {
+            let epoch_sold = EPOCH_SOLD.may_load(storage, (market.id, epoch))?.unwrap_or_default();
+            Some(epoch_capacity.saturating_sub(epoch_sold).min(market_remaining))
+        }
+        None => None,
+    };
+
+    let mut address_remaining = None;
+    let mut allowlisted = !limits.allowlist_enabled;
+    if let Some(address) = address {
+        if let Some(address_cap) = limits.address_cap {
+            let address_sold = ADDRESS_EPOCH_SOLD
+                .may_load(storage, (market.id, epoch, &address))?
+                .unwrap_or_default();
+            address_remaining = Some(address_cap.saturating_sub(address_sold));
+        }
+        allowlisted = allowlisted || ALLOWLIST.has(storage, (market.id, &address));
+    }
+
+    Ok(RemainingCapacityResponse {
+        market_id: market.id,
+        epoch,
+        epoch_end,
+        market_remaining,
+        epoch_remaining,
+        address_remaining,
+        allowlisted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cw20::Denom;
+
+    use crate::state::MarketLimits;
+
+    fn market() -> Market {
+        Market {
+            id: 1,
+            asset: Denom::Native("uusdc".to_string()),
+            pool_address: None,
+            is_lp: false,
+            discount: 50,
+            vesting_term: 1000,
+            capacity: Uint128::new(10_000),
+            sold: Uint128::zero(),
+            start_time: 1000,
+            end_time: 2000,
+            closed: false,
+        }
+    }
+
+    fn limits(allowlist_enabled: bool) -> MarketLimits {
+        MarketLimits {
+            epoch_length: 100,
+            epoch_capacity: Some(Uint128::new(1000)),
+            address_cap: Some(Uint128::new(600)),
+            allowlist_enabled,
+        }
+    }
+
+    #[test]
+    fn no_limits_pass() {
+        let mut storage = MockStorage::new();
+        let alice = Addr::unchecked("alice");
+        apply_market_limits(&mut storage, &market(), &alice, 1000, Uint128::new(1_000_000)).unwrap();
+        assert!(!EPOCH_SOLD.has(&storage, (1, 0)));
+    }
+
+    #[test]
+    fn epoch_and_address_caps() {
+        let mut storage = MockStorage::new();
+        let market = market();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        MARKET_LIMITS.save(&mut storage, market.id, &limits(false)).unwrap();
+
+        apply_market_limits(&mut storage, &market, &alice, 1000, Uint128::new(600)).unwrap();
+        assert_eq!(
+            apply_market_limits(&mut storage, &market, &alice, 1050, Uint128::new(1)).unwrap_err(),
+            ContractError::AddressCapExceeded { epoch: 0, remaining: Uint128::zero() }
+        );
+        assert_eq!(
+            apply_market_limits(&mut storage, &market, &bob, 1050, Uint128::new(500)).unwrap_err(),
+            ContractError::EpochCapacityExceeded { epoch: 0, remaining: Uint128::new(400) }
+        );
+        // rejected payouts are not counted
+        apply_market_limits(&mut storage, &market, &bob, 1099, Uint128::new(400)).unwrap();
+        assert_eq!(EPOCH_SOLD.load(&storage, (market.id, 0)).unwrap(), Uint128::new(1000));
+
+        // both caps start over in the next epoch
+        apply_market_limits(&mut storage, &market, &alice, 1100, Uint128::new(600)).unwrap();
+        assert_eq!(EPOCH_SOLD.load(&storage, (market.id, 1)).unwrap(), Uint128::new(600));
+        assert_eq!(ADDRESS_EPOCH_SOLD.load(&storage, (market.id, 1, &alice)).unwrap(), Uint128::new(600));
+    }
+
+    #[test]
+    fn allowlist_gates_bonding() {
+        let mut storage = MockStorage::new();
+        let market = market();
+        let alice = Addr::unchecked("alice");
+        MARKET_LIMITS.save(&mut storage, market.id, &limits(true)).unwrap();
+
+        assert_eq!(
+            apply_market_limits(&mut storage, &market, &alice, 1000, Uint128::new(100)).unwrap_err(),
+            ContractError::NotAllowlisted { market_id: market.id, address: alice.to_string() }
+        );
+        ALLOWLIST.save(&mut storage, (market.id, &alice), &true).unwrap();
+        apply_market_limits(&mut storage, &market, &alice, 1000, Uint128::new(100)).unwrap();
+    }
+}
@@ -1,49 +1,337 @@
 // msg.rs
 
-use cosmwasm_std::{Addr, Uint128};
-use cw20::Denom;
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::{Cw20ReceiveMsg, Denom, Expiration};
+
+use crate::state::{BondHistoryEntry, BondTier, DenomStats, MarketLimits, MarketStats, PenaltyDestination, PriceSource, WithdrawProposal};
+pub use crate::state::Market;
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct InstantiateMsg {
+    pub owner: Addr,
+    pub pool_address: Addr,
+    pub treasury_address: Addr,
+    pub fury_token_address: Addr,
+    pub term: u64,
+    pub discount: u64,
+    pub usdc_denom: String,
+    pub is_native_bonding: bool,
+    pub tx_fee: u64,
+    pub platform_fee: u64,
+    pub price_source: PriceSource,
+    pub max_price_age: u64,
+    pub max_price_deviation: u64,
+    pub control_variable: u64,
+    pub min_price: Decimal,
+    pub max_debt: Option<Uint128>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum ExecuteMsg {
     UpdateOwner { owner: Addr },
     UpdateEnabled { enabled: bool },
     UpdateConfig {
-        lock_days: u64,
+        term: u64,
         discount: u64,
         tx_fee: u64,
         platform_fee: u64,
     },
-    Bond {},
-    LpBond { address: Addr, amount: Uint128 },
-    Unbond { index: u64 },
-    Withdraw { amount: Uint128 },
+    UpdatePriceSource {
+        price_source: PriceSource,
+        max_price_age: u64,
+        max_price_deviation: u64,
+    },
+    UpdateBondPricing {
+        control_variable: u64,
+        min_price: Decimal,
+        max_debt: Option<Uint128>,
+    },
+    CreateMarket {
+        asset: Denom,
+        pool_address: Option<Addr>,
+        is_lp: bool,
+        discount: u64,
+        vesting_term: u64,
+        capacity: Uint128,
+        start_time: u64,
+        end_time: u64,
+    },
+    UpdateMarket {
+        market_id: u64,
+        pool_address: Option<Addr>,
+        discount: Option<u64>,
+        vesting_term: Option<u64>,
+        capacity: Option<Uint128>,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+    },
+    CloseMarket { market_id: u64 },
+    UpdateMarketLimits {
+        market_id: u64,
+        limits: Option<MarketLimits>,
+    },
+    UpdateAllowlist {
+        market_id: u64,
+        add: Vec<Addr>,
+        remove: Vec<Addr>,
+    },
+    Receive(Cw20ReceiveMsg),
+    Bond {
+        market_id: Option<u64>,
+        min_payout: Option<Uint128>,
+        expiration: Option<Expiration>,
+        referrer: Option<Addr>,
+        // Only for bonds outside markets
+        tier: Option<u32>,
+    },
+    // amount is the Fury worth of the deposit, lp_amount the LP tokens it minted
+    LpBond {
+        address: Addr,
+        amount: Uint128,
+        #[serde(default)]
+        lp_amount: Uint128,
+    },
+    UpdateEarlyUnbond {
+        max_penalty: u64,
+        destination: PenaltyDestination,
+    },
+    Claim {
+        bond_id: u64,
+        #[serde(default)]
+        stake_on_claim: bool,
+    },
+    EarlyUnbond { bond_id: u64 },
+    ClaimAll {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        #[serde(default)]
+        stake_on_claim: bool,
+    },
+    UpdateStaking { address: Option<Addr> },
+    UpdateClaimOperator { operator: Addr, approved: bool },
+    UpdateClaimSettings {
+        keeper_claims: bool,
+        recipient: Option<Addr>,
+    },
+    UpdateKeeperTip { tip: Uint128 },
+    UpdatePolRatio { pol_ratio: u64 },
+    UpdateReferralShare { referral_share: u64 },
+    UpdateTiers { tiers: Vec<BondTier> },
+    ClaimFor {
+        owner: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    CreateBondNft {
+        code_id: u64,
+        name: String,
+        symbol: String,
+    },
+    SyncBondOwner { bond_id: u64 },
+    UpdateWithdrawDelay { delay: u64 },
+    ProposeWithdraw {
+        denom: Denom,
+        amount: Uint128,
+        recipient: Addr,
+    },
+    ExecuteWithdraw {},
+    CancelWithdraw {},
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub enum QueryMsg {
     Config {},
-    BondState { address: Addr },
-    AllBondState { start_after: Option<String>, limit: Option<u32> },
+    Bond { bond_id: u64 },
+    BondState { address: Addr, start_after: Option<u64>, limit: Option<u32> },
+    AllBondState { start_after: Option<u64>, limit: Option<u32> },
+    Claimable { address: Addr, start_after: Option<u64>, limit: Option<u32> },
+    BondPrice {},
+    Market { market_id: u64 },
+    Markets { start_after: Option<u64>, limit: Option<u32> },
+    Inventory {},
+    BondQuote { market_id: Option<u64>, tier: Option<u32>, amount: Uint128 },
+    EarlyUnbondPreview { bond_id: u64 },
+    BondNft {},
+    Staking {},
+    Stats {},
+    MarketStats { market_id: u64 },
+    MarketLimits { market_id: u64 },
+    RemainingCapacity { market_id: u64, address: Option<Addr> },
+    PendingWithdraw {},
+    ClaimSettings { address: Addr },
+    KeeperTip {},
+    Pol {},
+    Referral { address: Addr },
+    Tiers {},
+    BondHistory {
+        address: Addr,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-pub enum MigrateMsg {
-    // Define migration-related messages if needed
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ReceiveMsg {
+    Bond {
+        market_id: u64,
+        min_payout: Option<Uint128>,
+        expiration: Option<Expiration>,
+        referrer: Option<Addr>,
+    },
+    Fund {},
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct MigrateMsg {}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct ConfigResponse {
     pub owner: Addr,
     pub pool_address: Addr,
     pub treasury_address: Addr,
     pub fury_token_address: Addr,
-    pub lock_days: u64,
+    pub term: u64,
     pub discount: u64,
     pub usdc_denom: String,
     pub is_native_bonding: bool,
     pub tx_fee: u64,
     pub platform_fee: u64,
     pub enabled: bool,
+    pub price_source: PriceSource,
+    pub max_price_age: u64,
+    pub max_price_deviation: u64,
+    pub control_variable: u64,
+    pub min_price: Decimal,
+    pub max_debt: Option<Uint128>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondPriceResponse {
+    pub discount: u64,
+    pub market_price: Decimal,
+    pub bond_price: Decimal,
+    pub current_debt: Uint128,
+    pub max_debt: Option<Uint128>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondQuoteResponse {
+    pub fee_amount: Uint128,
+    pub bond_amount: Uint128,
+    // Input per Fury at the pool, before the discount
+    pub market_price: Decimal,
+    pub tier: Option<u32>,
+    pub discount: u64,
+    pub payout: Uint128,
+    pub unlock_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct EarlyUnbondPreviewResponse {
+    pub bond_id: u64,
+    pub payout: Uint128,
+    pub penalty: Uint128,
+    pub destination: PenaltyDestination,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondNftResponse {
+    pub address: Option<Addr>,
+    pub first_bond_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct StakingResponse {
+    pub address: Option<Addr>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StatsResponse {
+    pub total_payout: Uint128,
+    pub fury_owed: Uint128,
+    pub total_claimed: Uint128,
+    pub total_penalty: Uint128,
+    pub active_bonds: u64,
+    pub denoms: Vec<DenomStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct MarketStatsResponse {
+    pub market_id: u64,
+    pub asset: Denom,
+    pub fury_owed: Uint128,
+    pub stats: MarketStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct MarketLimitsResponse {
+    pub market_id: u64,
+    pub limits: Option<MarketLimits>,
+}
+
+// Fury payout a market can still sell, overall, in the current epoch and to one address
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RemainingCapacityResponse {
+    pub market_id: u64,
+    pub epoch: u64,
+    pub epoch_end: u64,
+    pub market_remaining: Uint128,
+    pub epoch_remaining: Option<Uint128>,
+    pub address_remaining: Option<Uint128>,
+    pub allowlisted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PendingWithdrawResponse {
+    pub delay: u64,
+    pub proposal: Option<WithdrawProposal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ClaimSettingsResponse {
+    pub address: Addr,
+    pub keeper_claims: bool,
+    pub recipient: Option<Addr>,
+    pub operators: Vec<Addr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct KeeperTipResponse {
+    pub tip: Uint128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct TiersResponse {
+    pub tiers: Vec<BondTier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ReferralResponse {
+    pub address: Addr,
+    pub referral_share: u64,
+    pub referred_bonds: u64,
+    pub total_rewards: Uint128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PolResponse {
+    pub pol_ratio: u64,
+    pub total_usdc: Uint128,
+    pub total_fury: Uint128,
+    pub total_lp: Uint128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct InventoryResponse {
+    pub committed: Uint128,
+    pub free: Uint128,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct MarketsResponse {
+    pub list: Vec<Market>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
@@ -53,12 +341,85 @@ pub struct BondStateResponse {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-pub struct AllBondStateResponse {
-    pub list: Vec<BondStateResponse>,
+pub struct ClaimableBond {
+    pub bond_id: u64,
+    pub amount: Uint128,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-pub struct BondingRecord {
+pub struct ClaimableResponse {
+    pub address: Addr,
     pub amount: Uint128,
-    pub timestamp: u64,
+    pub list: Vec<ClaimableBond>,
 }
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct BondHistoryResponse {
+    pub address: Addr,
+    pub list: Vec<BondHistoryEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct AllBondStateResponse {
+    pub list: Vec<BondingRecord>,
+}
+
+// Queries the fanfuryswap pool exposes on top of the wasmswap ones
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolQueryMsg {
+    Twap { window: u64 },
+}
+
+// fanfuryswap AddLiquidity, the pool mints LP for the bonding contract to the treasury
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolExecuteMsg {
+    AddLiquidity {
+        token1_amount: Uint128,
+        min_liquidity: Uint128,
+        max_token2: Uint128,
+        fee_amount: Uint128,
+        expiration: Option<Expiration>,
+    },
+}
+
+// Fields of the wasmswap Info response used to check a market pool
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct PoolInfoResponse {
+    pub token1_reserve: Uint128,
+    pub token1_denom: Denom,
+    pub token2_reserve: Uint128,
+    pub token2_denom: Denom,
+    pub lp_token_supply: Uint128,
+    pub lp_token_address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PoolTwapResponse {
+    pub price: Decimal,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+// Interface an external oracle contract has to implement, price is usdc per Fury
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    Price {},
+}
+
+// Hook sent along with the payout to the staking contract, staking it on behalf of address
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingHookMsg {
+    Stake { address: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct OraclePriceResponse {
+    pub price: Decimal,
+    pub updated_at: u64,
+}
+
+pub use crate::state::BondingRecord;
@@ -0,0 +1,540 @@
+use std::convert::TryFrom;
+
+use cosmwasm_std::{Addr, Decimal, Deps, Env, StdError, Uint128, Uint256};
+use cw20::Denom;
+use wasmswap::msg::{InfoResponse as WasmswapInfoResponse, QueryMsg as WasmswapQueryMsg};
+
+use crate::error::ContractError;
+use crate::msg::{OraclePriceResponse, OracleQueryMsg, PoolQueryMsg, PoolTwapResponse};
+use crate::state::{Config, PriceSource};
+
+// Price of one Fury in usdc from the configured source, checked for staleness and deviation
+pub fn query_fury_price(deps: Deps, env: &Env, cfg: &Config) -> Result<Decimal, ContractError> {
+    let spot = query_spot_price(deps, cfg)?;
+    let now = env.block.time.seconds();
+
+    let price = match cfg.price_source.clone() {
+        PriceSource::PoolSpot {} => return Ok(spot),
+        PriceSource::PoolTwap { window } => query_twap(deps, &cfg.pool_address, window, now)?,
+        PriceSource::Oracle { address } => {
+            let oracle: OraclePriceResponse = deps
+                .querier
+                .query_wasm_smart(&address, &OracleQueryMsg::Price {})?;
+            if oracle.updated_at + cfg.max_price_age < now {
+                return Err(ContractError::StalePrice {
+                    updated_at: oracle.updated_at,
+                    max_age: cfg.max_price_age,
+                });
+            }
+            oracle.price
+        }
+    };
+
+    check_deviation(price, spot, cfg.max_price_deviation)?;
+    Ok(price)
+}
+
+// Window used to price the Fury leg of LP tokens when the config does not use a TWAP
+const DEFAULT_TWAP_WINDOW: u64 = 3600;
+
+pub fn query_twap(deps: Deps, pool_address: &Addr, window: u64, now: u64) -> Result<Decimal, ContractError> {
+    let twap: PoolTwapResponse = deps
+        .querier
+        .query_wasm_smart(pool_address, &PoolQueryMsg::Twap { window })?;
+    // reserve history does not reach back over the whole window yet
+    if twap.start_time > now.saturating_sub(window) {
+        return Err(ContractError::StalePrice {
+            updated_at: twap.start_time,
+            max_age: window,
+        });
+    }
+    Ok(twap.price)
+}
+
+// Fury worth of `amount` LP tokens at fair reserves: with k = x * y and the pool TWAP p in usdc
+// per Fury, the pool holds sqrt(k / p) Fury and the same value in usdc, so the pool is worth
+// 2 * sqrt(k / p) Fury. Spot reserves only enter through k, which a swap cannot shrink.
+pub fn query_lp_fury_value(
+    deps: Deps,
+    env: &Env,
+    cfg: &Config,
+    pool_address: &Addr,
+    lp_token: &Denom,
+    amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let info: WasmswapInfoResponse = deps
+        .querier
+        .query_wasm_smart(pool_address, &WasmswapQueryMsg::Info {})?;
+    if Denom::Cw20(Addr::unchecked(info.lp_token_address.clone())) != *lp_token {
+        return Err(ContractError::UnacceptableToken {});
+    }
+    if info.lp_token_supply.is_zero() {
+        return Err(ContractError::NoLiquidity {});
+    }
+
+    let window = match cfg.price_source {
+        PriceSource::PoolTwap { window } => window,
+        _ => DEFAULT_TWAP_WINDOW,
+    };
+    let twap = query_twap(deps, pool_address, window, env.block.time.seconds())?;
+    if twap.is_zero() {
+        return Err(ContractError::NoLiquidity {});
+    }
+
+    let k = Uint256::from(info.token1_reserve) * Uint256::from(info.token2_reserve);
+    let fair_fury = isqrt(k * Uint256::from(Decimal::one().atomics()) / Uint256::from(twap.atomics()));
+    let value = Uint256::from(amount) * fair_fury * Uint256::from(2u8) / Uint256::from(info.lp_token_supply);
+    Ok(Uint128::try_from(value).map_err(StdError::from)?)
+}
+
+// Integer square root, rounded down
+fn isqrt(n: Uint256) -> Uint256 {
+    if n.is_zero() {
+        return n;
+    }
+    let two = Uint256::from(2u8);
+    let mut x = n;
+    let mut y = (x + Uint256::one()) / two;
+    while y < x {
+        x = y;
+        y = (x + n / x) / two;
+    }
+    x
+}
+
+pub fn query_spot_price(deps: Deps, cfg: &Config) -> Result<Decimal, ContractError> {
+    let info: WasmswapInfoResponse = deps
+        .querier
+        .query_wasm_smart(&cfg.pool_address, &WasmswapQueryMsg::Info {})?;
+    if info.token2_reserve.is_zero() {
+        return Err(ContractError::NoLiquidity {});
+    }
+    Ok(Decimal::from_ratio(info.token1_reserve, info.token2_reserve))
+}
+
+fn check_deviation(price: Decimal, spot: Decimal, max_deviation: u64) -> Result<(), ContractError> {
+    if max_deviation == 0 {
+        return Ok(());
+    }
+    let diff = if price > spot { price - spot } else { spot - price };
+    if diff > spot * Decimal::permille(max_deviation) {
+        return Err(ContractError::PriceDeviation { price, spot });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{MockApi, MockStorage};
+    use cosmwasm_std::{coin, coins, to_binary, Binary, DepsMut, Empty, MessageInfo, Response, StdError, StdResult};
+    use cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20ReceiveMsg};
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+    use cw_storage_plus::Item;
+
+    use crate::msg::{ExecuteMsg, InstantiateMsg, OraclePriceResponse, PoolTwapResponse, ReceiveMsg};
+    use crate::state::PenaltyDestination;
+
+    const ORACLE_PRICE: Item<OraclePriceResponse> = Item::new("price");
+    const POOL_STATE: Item<MockPool> = Item::new("pool");
+
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+    struct MockPool {
+        token1_reserve: Uint128,
+        token2_reserve: Uint128,
+        twap: PoolTwapResponse,
+    }
+
+    // Subset of the wasmswap Info response the bonding contract reads
+    #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct MockInfoResponse {
+        token1_reserve: Uint128,
+        token1_denom: Denom,
+        token2_reserve: Uint128,
+        token2_denom: Denom,
+        lp_token_supply: Uint128,
+        lp_token_address: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+    #[serde(rename_all = "snake_case")]
+    enum MockPoolQuery {
+        Info {},
+        Twap { window: u64 },
+    }
+
+    fn mock_execute(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+        Err(StdError::generic_err("not supported"))
+    }
+
+    fn oracle_instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: OraclePriceResponse) -> StdResult<Response> {
+        ORACLE_PRICE.save(deps.storage, &msg)?;
+        Ok(Response::default())
+    }
+
+    fn oracle_query(deps: Deps, _env: Env, _msg: OracleQueryMsg) -> StdResult<Binary> {
+        to_binary(&ORACLE_PRICE.load(deps.storage)?)
+    }
+
+    fn pool_instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: MockPool) -> StdResult<Response> {
+        POOL_STATE.save(deps.storage, &msg)?;
+        Ok(Response::default())
+    }
+
+    fn pool_query(deps: Deps, _env: Env, msg: MockPoolQuery) -> StdResult<Binary> {
+        let pool = POOL_STATE.load(deps.storage)?;
+        match msg {
+            MockPoolQuery::Info {} => to_binary(&MockInfoResponse {
+                token1_reserve: pool.token1_reserve,
+                token1_denom: Denom::Native("uusdc".to_string()),
+                token2_reserve: pool.token2_reserve,
+                token2_denom: Denom::Cw20(Addr::unchecked("fury")),
+                lp_token_supply: Uint128::new(1_000_000),
+                lp_token_address: "lp".to_string(),
+            }),
+            MockPoolQuery::Twap { .. } => to_binary(&pool.twap),
+        }
+    }
+
+    fn mock_oracle() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(mock_execute, oracle_instantiate, oracle_query))
+    }
+
+    fn mock_pool() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(mock_execute, pool_instantiate, pool_query))
+    }
+
+    // Pool with two hours of reserve history behind its TWAP
+    fn instantiate_pool(app: &mut App, token1_reserve: u128, token2_reserve: u128, twap: Decimal) -> Addr {
+        let pool_id = app.store_code(mock_pool());
+        let now = app.block_info().time.seconds();
+        app.instantiate_contract(
+            pool_id,
+            Addr::unchecked("owner"),
+            &MockPool {
+                token1_reserve: Uint128::new(token1_reserve),
+                token2_reserve: Uint128::new(token2_reserve),
+                twap: PoolTwapResponse {
+                    price: twap,
+                    start_time: now - 7200,
+                    end_time: now,
+                },
+            },
+            &[],
+            "pool",
+            None,
+        )
+        .unwrap()
+    }
+
+    // Pool priced at 2 usdc per Fury and an oracle reporting `price` at `updated_at`
+    fn setup(price: Decimal, updated_at: u64) -> (App, Addr, Addr) {
+        let mut app = App::default();
+        let owner = Addr::unchecked("owner");
+        let pool = instantiate_pool(&mut app, 2_000_000, 1_000_000, Decimal::percent(210));
+
+        let oracle_id = app.store_code(mock_oracle());
+        let oracle = app
+            .instantiate_contract(
+                oracle_id,
+                owner,
+                &OraclePriceResponse { price, updated_at },
+                &[],
+                "oracle",
+                None,
+            )
+            .unwrap();
+
+        (app, pool, oracle)
+    }
+
+    fn fury_token() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        ))
+    }
+
+    fn bonding_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(crate::contract::execute, crate::contract::instantiate, crate::contract::query))
+    }
+
+    // Bonding contract priced by the oracle, holding a one day 10_000 Fury LP bond for "bonder".
+    // The oracle reports `price` at `updated_at` seconds after the bond was made.
+    fn bonded(price: Decimal, updated_at: u64) -> (App, Addr, Addr) {
+        let now = App::default().block_info().time.seconds();
+        let (mut app, pool, oracle) = setup(price, now + updated_at);
+        let owner = Addr::unchecked("owner");
+        let bonder = Addr::unchecked("bonder");
+        app.init_modules(|router, _, storage| router.bank.init_balance(storage, &bonder, coins(1_000, "uusdc")))
+            .unwrap();
+
+        let fury_id = app.store_code(fury_token());
+        let fury = app
+            .instantiate_contract(
+                fury_id,
+                owner.clone(),
+                &cw20_base::msg::InstantiateMsg {
+                    name: "Fury".to_string(),
+                    symbol: "FURY".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![Cw20Coin { address: owner.to_string(), amount: Uint128::new(100_000) }],
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                "fury",
+                None,
+            )
+            .unwrap();
+
+        let bonding_id = app.store_code(bonding_contract());
+        let bonding = app
+            .instantiate_contract(
+                bonding_id,
+                owner.clone(),
+                &InstantiateMsg {
+                    owner: owner.clone(),
+                    pool_address: pool.clone(),
+                    treasury_address: Addr::unchecked("treasury"),
+                    fury_token_address: fury.clone(),
+                    term: 86400,
+                    discount: 50,
+                    usdc_denom: "uusdc".to_string(),
+                    is_native_bonding: false,
+                    tx_fee: 10,
+                    platform_fee: 10,
+                    price_source: PriceSource::Oracle { address: oracle },
+                    max_price_age: 300,
+                    max_price_deviation: 50,
+                    control_variable: 0,
+                    min_price: Decimal::zero(),
+                    max_debt: None,
+                },
+                &[],
+                "bonding",
+                None,
+            )
+            .unwrap();
+
+        // the bonding messages are not snake_case, so the cw20 hook is delivered by hand
+        app.execute_contract(
+            owner.clone(),
+            fury.clone(),
+            &Cw20ExecuteMsg::Transfer { recipient: bonding.to_string(), amount: Uint128::new(100_000) },
+            &[],
+        )
+        .unwrap();
+        app.execute_contract(
+            fury.clone(),
+            bonding.clone(),
+            &ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: owner.to_string(),
+                amount: Uint128::new(100_000),
+                msg: to_binary(&ReceiveMsg::Fund {}).unwrap(),
+            }),
+            &[],
+        )
+        .unwrap();
+        // 9_500 Fury bonded at a 5% discount pays out 10_000
+        app.execute_contract(
+            pool,
+            bonding.clone(),
+            &ExecuteMsg::LpBond { address: bonder, amount: Uint128::new(9_500), lp_amount: Uint128::new(1_000) },
+            &[],
+        )
+        .unwrap();
+        (app, bonding, fury)
+    }
+
+    fn matured_bond(price: Decimal, updated_at: u64) -> (App, Addr) {
+        let (mut app, bonding, _) = bonded(price, updated_at);
+        app.update_block(|block| {
+            block.time = block.time.plus_seconds(86400);
+            block.height += 86400 / 5;
+        });
+        (app, bonding)
+    }
+
+    fn claim(app: &mut App, bonding: &Addr, usdc: u128) -> Result<(), ContractError> {
+        app.execute_contract(
+            Addr::unchecked("bonder"),
+            bonding.clone(),
+            &ExecuteMsg::Claim { bond_id: 1, stake_on_claim: false },
+            &coins(usdc, "uusdc"),
+        )
+        .map(|_| ())
+        .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn config(pool: Addr, price_source: PriceSource, max_price_age: u64, max_price_deviation: u64) -> Config {
+        Config {
+            owner: Addr::unchecked("owner"),
+            pool_address: pool,
+            treasury_address: Addr::unchecked("treasury"),
+            fury_token_address: Addr::unchecked("fury"),
+            term: 5 * 86400,
+            discount: 50,
+            usdc_denom: "uusdc".to_string(),
+            is_native_bonding: true,
+            tx_fee: 10,
+            platform_fee: 10,
+            enabled: true,
+            price_source,
+            max_price_age,
+            max_price_deviation,
+            control_variable: 0,
+            min_price: Decimal::zero(),
+            max_debt: None,
+        }
+    }
+
+    fn fury_price(app: &App, cfg: &Config) -> Result<Decimal, ContractError> {
+        let storage = MockStorage::new();
+        let api = MockApi::default();
+        let deps = Deps { storage: &storage, api: &api, querier: app.wrap() };
+        let env = Env { block: app.block_info(), ..cosmwasm_std::testing::mock_env() };
+        query_fury_price(deps, &env, cfg)
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        assert_eq!(isqrt(Uint256::zero()), Uint256::zero());
+        assert_eq!(isqrt(Uint256::from(1u8)), Uint256::from(1u8));
+        assert_eq!(isqrt(Uint256::from(15u8)), Uint256::from(3u8));
+        assert_eq!(isqrt(Uint256::from(16u8)), Uint256::from(4u8));
+    }
+
+    #[test]
+    fn lp_value_uses_fair_reserves() {
+        let mut app = App::default();
+        // reserves pushed to a spot of 8 while the TWAP stays at 2, k = 2e12
+        let pool = instantiate_pool(&mut app, 4_000_000, 500_000, Decimal::percent(200));
+        let cfg = config(pool.clone(), PriceSource::PoolSpot {}, 0, 0);
+        let lp_token = Denom::Cw20(Addr::unchecked("lp"));
+
+        // at fair reserves the pool holds 1_000_000 Fury and as much value in usdc,
+        // a tenth of the supply is worth 200_000 Fury where spot reserves would give 250_000
+        let storage = MockStorage::new();
+        let api = MockApi::default();
+        let deps = Deps { storage: &storage, api: &api, querier: app.wrap() };
+        let env = Env { block: app.block_info(), ..cosmwasm_std::testing::mock_env() };
+        let value = query_lp_fury_value(deps, &env, &cfg, &pool, &lp_token, Uint128::new(100_000)).unwrap();
+        assert_eq!(value, Uint128::new(200_000));
+
+        assert_eq!(
+            query_lp_fury_value(deps, &env, &cfg, &pool, &Denom::Cw20(Addr::unchecked("other")), Uint128::new(100_000))
+                .unwrap_err(),
+            ContractError::UnacceptableToken {}
+        );
+    }
+
+    #[test]
+    fn pool_spot_price() {
+        let (app, pool, _) = setup(Decimal::percent(200), 0);
+        let cfg = config(pool, PriceSource::PoolSpot {}, 0, 0);
+        assert_eq!(fury_price(&app, &cfg).unwrap(), Decimal::percent(200));
+    }
+
+    #[test]
+    fn fresh_oracle_price() {
+        let now = App::default().block_info().time.seconds();
+        let (app, pool, oracle) = setup(Decimal::percent(205), now - 60);
+        let cfg = config(pool, PriceSource::Oracle { address: oracle }, 300, 50);
+        assert_eq!(fury_price(&app, &cfg).unwrap(), Decimal::percent(205));
+    }
+
+    #[test]
+    fn stale_oracle_price_rejected() {
+        let now = App::default().block_info().time.seconds();
+        let (app, pool, oracle) = setup(Decimal::percent(200), now - 301);
+        let cfg = config(pool, PriceSource::Oracle { address: oracle }, 300, 0);
+        assert_eq!(
+            fury_price(&app, &cfg).unwrap_err(),
+            ContractError::StalePrice { updated_at: now - 301, max_age: 300 }
+        );
+    }
+
+    #[test]
+    fn oracle_deviation_rejected() {
+        let now = App::default().block_info().time.seconds();
+        // 2.2 against a spot of 2 is 100 per mille away
+        let (app, pool, oracle) = setup(Decimal::percent(220), now);
+        let cfg = config(pool.clone(), PriceSource::Oracle { address: oracle.clone() }, 300, 50);
+        assert_eq!(
+            fury_price(&app, &cfg).unwrap_err(),
+            ContractError::PriceDeviation { price: Decimal::percent(220), spot: Decimal::percent(200) }
+        );
+
+        // the same price passes once the allowed deviation covers it, or with the check off
+        let cfg = config(pool.clone(), PriceSource::Oracle { address: oracle.clone() }, 300, 100);
+        assert_eq!(fury_price(&app, &cfg).unwrap(), Decimal::percent(220));
+        let cfg = config(pool, PriceSource::Oracle { address: oracle }, 300, 0);
+        assert_eq!(fury_price(&app, &cfg).unwrap(), Decimal::percent(220));
+    }
+
+    #[test]
+    fn twap_price_checked_against_spot() {
+        let (app, pool, _) = setup(Decimal::percent(200), 0);
+        // the mock TWAP of 2.1 is 50 per mille off spot
+        let cfg = config(pool.clone(), PriceSource::PoolTwap { window: 3600 }, 0, 50);
+        assert_eq!(fury_price(&app, &cfg).unwrap(), Decimal::percent(210));
+        let cfg = config(pool.clone(), PriceSource::PoolTwap { window: 3600 }, 0, 40);
+        assert_eq!(
+            fury_price(&app, &cfg).unwrap_err(),
+            ContractError::PriceDeviation { price: Decimal::percent(210), spot: Decimal::percent(200) }
+        );
+
+        // history only covers two hours
+        let cfg = config(pool, PriceSource::PoolTwap { window: 3 * 3600 }, 0, 0);
+        assert!(matches!(fury_price(&app, &cfg).unwrap_err(), ContractError::StalePrice { .. }));
+    }
+
+    #[test]
+    fn claim_pays_fee_on_oracle_price() {
+        // 10_000 Fury at 2.05 is worth 20_500 usdc, 2% of it is due as fee
+        let (mut app, bonding) = matured_bond(Decimal::percent(205), 86400 - 60);
+        assert_eq!(claim(&mut app, &bonding, 409).unwrap_err(), ContractError::InsufficientFee {});
+        claim(&mut app, &bonding, 410).unwrap();
+        assert_eq!(app.wrap().query_balance("treasury", "uusdc").unwrap(), coin(410, "uusdc"));
+    }
+
+    #[test]
+    fn claim_rejects_bad_oracle_price() {
+        // reported when the bond was made, a day before the claim
+        let (mut app, bonding) = matured_bond(Decimal::percent(205), 0);
+        assert_eq!(
+            claim(&mut app, &bonding, 1_000).unwrap_err(),
+            ContractError::StalePrice { updated_at: App::default().block_info().time.seconds(), max_age: 300 }
+        );
+
+        // 2.2 against a spot of 2 is 100 per mille away
+        let (mut app, bonding) = matured_bond(Decimal::percent(220), 86400 - 60);
+        assert_eq!(
+            claim(&mut app, &bonding, 1_000).unwrap_err(),
+            ContractError::PriceDeviation { price: Decimal::percent(220), spot: Decimal::percent(200) }
+        );
+    }
+
+    #[test]
+    fn full_penalty_unbond_pays_nothing() {
+        let (mut app, bonding, fury) = bonded(Decimal::percent(205), 0);
+        app.execute_contract(
+            Addr::unchecked("owner"),
+            bonding.clone(),
+            &ExecuteMsg::UpdateEarlyUnbond { max_penalty: 1000, destination: PenaltyDestination::Treasury {} },
+            &[],
+        )
+        .unwrap();
+
+        // nothing is paid out, so no fee is due and no zero transfer is sent
+        app.execute_contract(Addr::unchecked("bonder"), bonding, &ExecuteMsg::EarlyUnbond { bond_id: 1 }, &[])
+            .unwrap();
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(fury, &cw20::Cw20QueryMsg::Balance { address: "treasury".to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(10_000));
+    }
+}
@@ -0,0 +1,180 @@
+use cosmwasm_std::{Decimal, StdResult, Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::state::{BondDebt, BondingRecord, Config, EarlyUnbondConfig, BOND_DEBT};
+use crate::util::THOUSAND;
+
+// Outstanding debt after linear decay since the last bond. All debt is pooled in one total, so it
+// decays over the config term even when tiers and markets vest over other terms: debt from longer
+// terms is released early and debt from shorter ones late, which only shifts the control premium.
+pub fn current_debt(storage: &dyn Storage, cfg: &Config, now: u64) -> StdResult<Uint128> {
+    let debt = match BOND_DEBT.may_load(storage)? {
+        Some(debt) => debt,
+        None => return Ok(Uint128::zero()),
+    };
+    let term = cfg.term;
+    let elapsed = now.saturating_sub(debt.last_decay);
+    if term == 0 || elapsed >= term {
+        return Ok(Uint128::zero());
+    }
+    let decay = debt.total_debt.multiply_ratio(elapsed, term);
+    Ok(debt.total_debt - decay)
+}
+
+// Discount per mille left after the control variable is applied to the debt ratio
+pub fn effective_discount(cfg: &Config, base_discount: u64, debt: Uint128) -> u64 {
+    let max_debt = match cfg.max_debt {
+        Some(max_debt) if !max_debt.is_zero() => max_debt,
+        _ => return base_discount,
+    };
+    let premium = Uint128::from(cfg.control_variable).multiply_ratio(debt.min(max_debt), max_debt);
+    base_discount.saturating_sub(premium.u128() as u64)
+}
+
+// Usdc per Fury a bond pays at, never below the configured minimum price
+pub fn bond_price(cfg: &Config, market_price: Decimal, discount: u64) -> Decimal {
+    let price = market_price * Decimal::permille(THOUSAND - discount);
+    if price < cfg.min_price {
+        cfg.min_price
+    } else {
+        price
+    }
+}
+
+// Caps a native bond payout so the bond never sells Fury below min_price
+pub fn apply_min_price(cfg: &Config, usdc_amount: Uint128, payout: Uint128) -> Uint128 {
+    if cfg.min_price.is_zero() {
+        return payout;
+    }
+    let max_payout = usdc_amount.multiply_ratio(Decimal::one().atomics(), cfg.min_price.atomics());
+    payout.min(max_payout)
+}
+
+// Payout and penalty for exiting a bond now, the penalty rate falls linearly with time remaining
+pub fn early_unbond_amounts(
+    record: &BondingRecord,
+    early_unbond: &EarlyUnbondConfig,
+    now: u64,
+) -> (Uint128, Uint128) {
+    let remaining = record.amount.saturating_sub(record.claimed);
+    let unvested = record.amount - record.vested(now);
+    let term = record.timestamp.saturating_sub(record.start_time);
+    if unvested.is_zero() || term == 0 {
+        return (remaining, Uint128::zero());
+    }
+    let time_left = record.timestamp.saturating_sub(now).min(term);
+    let penalty = unvested
+        .multiply_ratio(early_unbond.max_penalty, THOUSAND)
+        .multiply_ratio(time_left, term);
+    (remaining - penalty, penalty)
+}
+
+// Decays the stored debt up to now and adds a new payout, rejecting it above max_debt
+pub fn add_debt(
+    storage: &mut dyn Storage,
+    cfg: &Config,
+    now: u64,
+    payout: Uint128,
+) -> Result<Uint128, ContractError> {
+    let total_debt = current_debt(storage, cfg, now)? + payout;
+    if let Some(max_debt) = cfg.max_debt {
+        if total_debt > max_debt {
+            return Err(ContractError::MaxDebtReached {
+                current: total_debt - payout,
+                max: max_debt,
+            });
+        }
+    }
+    BOND_DEBT.save(
+        storage,
+        &BondDebt {
+            total_debt,
+            last_decay: now,
+        },
+    )?;
+    Ok(total_debt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::Addr;
+
+    use crate::state::{PenaltyDestination, PriceSource};
+
+    fn config(control_variable: u64, max_debt: Option<u128>) -> Config {
+        Config {
+            owner: Addr::unchecked("owner"),
+            pool_address: Addr::unchecked("pool"),
+            treasury_address: Addr::unchecked("treasury"),
+            fury_token_address: Addr::unchecked("fury"),
+            term: 1000,
+            discount: 100,
+            usdc_denom: "uusdc".to_string(),
+            is_native_bonding: true,
+            tx_fee: 10,
+            platform_fee: 10,
+            enabled: true,
+            price_source: PriceSource::PoolSpot {},
+            max_price_age: 0,
+            max_price_deviation: 0,
+            control_variable,
+            min_price: Decimal::zero(),
+            max_debt: max_debt.map(Uint128::new),
+        }
+    }
+
+    fn record(amount: u128, claimed: u128, start_time: u64, timestamp: u64) -> BondingRecord {
+        BondingRecord {
+            id: 1,
+            owner: Addr::unchecked("owner"),
+            market_id: None,
+            tier: None,
+            amount: Uint128::new(amount),
+            claimed: Uint128::new(claimed),
+            start_time,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn discount_falls_with_debt_ratio() {
+        // no ceiling, no control
+        assert_eq!(effective_discount(&config(40, None), 100, Uint128::new(500)), 100);
+        assert_eq!(effective_discount(&config(40, Some(0)), 100, Uint128::new(500)), 100);
+
+        let cfg = config(40, Some(1000));
+        assert_eq!(effective_discount(&cfg, 100, Uint128::zero()), 100);
+        assert_eq!(effective_discount(&cfg, 100, Uint128::new(500)), 80);
+        assert_eq!(effective_discount(&cfg, 100, Uint128::new(1000)), 60);
+        // the debt ratio is capped at one
+        assert_eq!(effective_discount(&cfg, 100, Uint128::new(3000)), 60);
+        // a premium above the discount leaves nothing
+        assert_eq!(effective_discount(&config(200, Some(1000)), 100, Uint128::new(1000)), 0);
+    }
+
+    #[test]
+    fn early_unbond_penalty_decays_to_maturity() {
+        let early_unbond = EarlyUnbondConfig {
+            max_penalty: 500,
+            destination: PenaltyDestination::Burn {},
+        };
+
+        // right after bonding the full rate applies to everything
+        let bond = record(1000, 0, 0, 1000);
+        assert_eq!(early_unbond_amounts(&bond, &early_unbond, 0), (Uint128::new(500), Uint128::new(500)));
+
+        // halfway, half the rate on the unvested half
+        assert_eq!(early_unbond_amounts(&bond, &early_unbond, 500), (Uint128::new(875), Uint128::new(125)));
+
+        // claimed Fury is not paid twice
+        let claimed = record(1000, 500, 0, 1000);
+        assert_eq!(early_unbond_amounts(&claimed, &early_unbond, 500), (Uint128::new(375), Uint128::new(125)));
+
+        // matured bonds and bonds without a term pay out in full
+        assert_eq!(early_unbond_amounts(&claimed, &early_unbond, 1000), (Uint128::new(500), Uint128::zero()));
+        let no_term = record(1000, 0, 100, 100);
+        assert_eq!(early_unbond_amounts(&no_term, &early_unbond, 50), (Uint128::new(1000), Uint128::zero()));
+    }
+
+}
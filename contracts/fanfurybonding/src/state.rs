@@ -1,8 +1,8 @@
 // state.rs
 
-use cosmwasm_std::{Addr, Storage, Uint128};
+use cosmwasm_std::{Addr, Decimal, StdResult, Storage, Uint128};
 use cw20::Denom;
-use cw_storage_plus::Item;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
 // Config struct to store contract configuration
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
@@ -11,60 +11,468 @@ pub struct Config {
     pub pool_address: Addr,
     pub treasury_address: Addr,
     pub fury_token_address: Addr,
-    pub lock_days: u64,
+    // Vesting term of bonds in seconds
+    pub term: u64,
     pub discount: u64,
     pub usdc_denom: String,
     pub is_native_bonding: bool,
     pub tx_fee: u64,
     pub platform_fee: u64,
     pub enabled: bool,
+    pub price_source: PriceSource,
+    // Oldest oracle price, in seconds, that is still accepted
+    pub max_price_age: u64,
+    // Max distance from the pool spot price, per mille, 0 disables the check
+    pub max_price_deviation: u64,
+    // Per mille taken off the discount for a full debt ratio
+    pub control_variable: u64,
+    // Lowest usdc price per Fury a native bond pays out at
+    pub min_price: Decimal,
+    // Ceiling on outstanding debt, no ceiling and no control when unset
+    pub max_debt: Option<Uint128>,
+}
+
+// Fury owed to bonds, decaying linearly over the vesting term
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondDebt {
+    pub total_debt: Uint128,
+    pub last_decay: u64,
+}
+
+pub const BOND_DEBT: Item<BondDebt> = Item::new("bond_debt");
+
+// Where the Fury price in usdc is read from
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum PriceSource {
+    PoolSpot {},
+    PoolTwap { window: u64 },
+    Oracle { address: Addr },
 }
 
 // Item to store the Config struct in storage
 pub const CONFIG: Item<Config> = Item::new("config");
 
+// Config as stored while the vesting term was counted in days, read by migrate. Deployments from
+// before price sources and bond pricing were added have none of the optional fields.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LegacyConfig {
+    pub owner: Addr,
+    pub pool_address: Addr,
+    pub treasury_address: Addr,
+    pub fury_token_address: Addr,
+    pub lock_days: u64,
+    pub discount: u64,
+    pub usdc_denom: String,
+    pub is_native_bonding: bool,
+    pub tx_fee: u64,
+    pub platform_fee: u64,
+    pub enabled: bool,
+    #[serde(default)]
+    pub price_source: Option<PriceSource>,
+    #[serde(default)]
+    pub max_price_age: u64,
+    #[serde(default)]
+    pub max_price_deviation: u64,
+    #[serde(default)]
+    pub control_variable: u64,
+    #[serde(default)]
+    pub min_price: Decimal,
+    #[serde(default)]
+    pub max_debt: Option<Uint128>,
+}
+
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+
 // BondingRecord struct to represent individual bonding records
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct BondingRecord {
+    pub id: u64,
+    pub owner: Addr,
+    pub market_id: Option<u64>,
+    // Index into the tier schedule chosen when bonding
+    #[serde(default)]
+    pub tier: Option<u32>,
     pub amount: Uint128,
+    pub claimed: Uint128,
+    // Vesting runs linearly from start_time until timestamp
+    pub start_time: u64,
     pub timestamp: u64,
 }
 
-// BONDING mapping to store bonding records for each address
-pub const BONDING: Item<Vec<BondingRecord>> = Item::new("bonding");
+impl BondingRecord {
+    pub fn vested(&self, now: u64) -> Uint128 {
+        if now >= self.timestamp {
+            self.amount
+        } else if now <= self.start_time {
+            Uint128::zero()
+        } else {
+            self.amount.multiply_ratio(now - self.start_time, self.timestamp - self.start_time)
+        }
+    }
+
+    pub fn claimable(&self, now: u64) -> Uint128 {
+        self.vested(now).saturating_sub(self.claimed)
+    }
+
+    pub fn is_fully_claimed(&self) -> bool {
+        self.claimed >= self.amount
+    }
+}
+
+// A bond market selling Fury for one accepted asset
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Market {
+    pub id: u64,
+    pub asset: Denom,
+    // wasmswap style pool pricing the asset as token1 against Fury, required unless the asset
+    // is usdc or the market takes LP tokens of the config pool
+    pub pool_address: Option<Addr>,
+    // The asset is the LP token of the pool, bonded as protocol owned liquidity
+    pub is_lp: bool,
+    pub discount: u64,
+    pub vesting_term: u64,
+    // Max Fury the market pays out over its lifetime
+    pub capacity: Uint128,
+    pub sold: Uint128,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub closed: bool,
+}
+
+impl Market {
+    pub fn is_open(&self, now: u64) -> bool {
+        !self.closed && now >= self.start_time && now < self.end_time
+    }
+}
+
+pub const MARKETS: Map<u64, Market> = Map::new("markets");
+pub const MARKET_COUNT: Item<u64> = Item::new("market_count");
+
+// Optional launch limits of a market, counted in Fury payout per epoch
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct MarketLimits {
+    // Epochs start at the market start_time, 0 makes the whole market one epoch
+    pub epoch_length: u64,
+    pub epoch_capacity: Option<Uint128>,
+    pub address_cap: Option<Uint128>,
+    // Only allowlisted addresses may bond when set
+    pub allowlist_enabled: bool,
+}
+
+impl MarketLimits {
+    pub fn epoch(&self, market: &Market, now: u64) -> u64 {
+        // a zero length keeps the whole market in one epoch
+        now.saturating_sub(market.start_time).checked_div(self.epoch_length).unwrap_or(0)
+    }
+}
+
+pub const MARKET_LIMITS: Map<u64, MarketLimits> = Map::new("market_limits");
+pub const ALLOWLIST: Map<(u64, &Addr), bool> = Map::new("allowlist");
+// Payout sold per (market, epoch) and per (market, epoch, address)
+pub const EPOCH_SOLD: Map<(u64, u64), Uint128> = Map::new("epoch_sold");
+pub const ADDRESS_EPOCH_SOLD: Map<(u64, u64, &Addr), Uint128> = Map::new("address_epoch_sold");
+
+// Where the Fury forfeited by early unbonding goes
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum PenaltyDestination {
+    Treasury {},
+    Burn {},
+}
+
+// Early unbonding is disabled until the owner configures it
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct EarlyUnbondConfig {
+    // Per mille of the unvested part forfeited right after bonding, falling to zero at maturity
+    pub max_penalty: u64,
+    pub destination: PenaltyDestination,
+}
+
+pub const EARLY_UNBOND: Item<EarlyUnbondConfig> = Item::new("early_unbond");
+
+// Fury held for payouts, split into what outstanding bonds are owed and what is still free
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct Inventory {
+    pub committed: Uint128,
+    pub free: Uint128,
+}
+
+pub const INVENTORY: Item<Inventory> = Item::new("inventory");
+
+// Owner withdrawal waiting out the timelock
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WithdrawProposal {
+    pub denom: Denom,
+    pub amount: Uint128,
+    pub recipient: Addr,
+    pub executable_at: u64,
+}
+
+pub const PENDING_WITHDRAW: Item<WithdrawProposal> = Item::new("pending_withdraw");
+// Seconds between proposing and executing a withdrawal
+pub const WITHDRAW_DELAY: Item<u64> = Item::new("withdraw_delay");
+pub const DEFAULT_WITHDRAW_DELAY: u64 = 2 * 86400;
+// Floor on the delay so the owner cannot shorten it and withdraw in the same block
+pub const MIN_WITHDRAW_DELAY: u64 = 86400;
+
+// cw721 companion contract minting one token per bond, token id is the bond id
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondNft {
+    pub address: Addr,
+    // Bonds created before the collection existed have no token and stay with their owner
+    pub first_bond_id: u64,
+}
+
+impl BondNft {
+    pub fn has_token(&self, bond_id: u64) -> bool {
+        bond_id >= self.first_bond_id
+    }
+}
+
+pub const BOND_NFT: Item<BondNft> = Item::new("bond_nft");
+
+// Protocol wide bonding totals, Fury still owed is the committed inventory
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct Stats {
+    pub total_payout: Uint128,
+    pub total_claimed: Uint128,
+    pub total_penalty: Uint128,
+    pub active_bonds: u64,
+}
+
+pub const STATS: Item<Stats> = Item::new("stats");
+
+// Bonded input and fees collected in one denom
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DenomStats {
+    pub denom: Denom,
+    pub bonded: Uint128,
+    pub fees: Uint128,
+}
+
+// Keyed by native denom or cw20 address
+pub const DENOM_STATS: Map<&str, DenomStats> = Map::new("denom_stats");
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct MarketStats {
+    pub bonded: Uint128,
+    pub payout: Uint128,
+    pub claimed: Uint128,
+    pub penalty: Uint128,
+    pub active_bonds: u64,
+}
+
+pub const MARKET_STATS: Map<u64, MarketStats> = Map::new("market_stats");
+
+// How others may claim an owner's bonds
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct ClaimSettings {
+    // Any keeper may claim matured bonds, earning the keeper tip
+    pub keeper_claims: bool,
+    // Where claims made by operators and keepers pay out, the owner when unset
+    pub recipient: Option<Addr>,
+}
+
+pub const CLAIM_SETTINGS: Map<&Addr, ClaimSettings> = Map::new("claim_settings");
+// (owner, operator) approvals to claim on the owner's behalf without a tip
+pub const CLAIM_OPERATORS: Map<(&Addr, &Addr), bool> = Map::new("claim_operators");
+// Fury a keeper earns per claim, taken from the payout
+pub const KEEPER_TIP: Item<Uint128> = Item::new("keeper_tip");
+
+// Per mille of usdc bond proceeds paired with free Fury and added to the pool, 0 sends all to the treasury
+pub const POL_RATIO: Item<u64> = Item::new("pol_ratio");
+
+// Protocol owned liquidity added to the pool so far, LP tokens are held by the treasury
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct PolStats {
+    pub total_usdc: Uint128,
+    pub total_fury: Uint128,
+    pub total_lp: Uint128,
+}
+
+pub const POL_STATS: Item<PolStats> = Item::new("pol_stats");
+
+// A bond option trading a longer term or a larger bond for a higher discount
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BondTier {
+    // Vesting term in seconds
+    pub term: u64,
+    // Smallest bond amount, after fees, the tier is open to
+    pub min_amount: Uint128,
+    pub discount: u64,
+}
+
+// Tiers users can pick from on native bonds, the config term and discount apply without one
+pub const TIERS: Item<Vec<BondTier>> = Item::new("tiers");
+
+// Per mille of a bond's discount paid to its referrer as an extra bond, 0 disables referrals
+pub const REFERRAL_SHARE: Item<u64> = Item::new("referral_share");
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+pub struct ReferralStats {
+    pub referred_bonds: u64,
+    pub total_rewards: Uint128,
+}
+
+pub const REFERRALS: Map<&Addr, ReferralStats> = Map::new("referrals");
 
-// Function to check if the contract is enabled
-pub fn check_enabled(storage: &dyn Storage) -> Result<(), ContractError> {
-    let cfg = CONFIG.load(storage)?;
-    if !cfg.enabled {
-        return Err(ContractError::Disabled {});
+// Staking contract claims can pay into, accepting Fury through cw20 Send
+pub const STAKING: Item<Addr> = Item::new("staking");
+
+// Last bond id handed out, ids are never reused
+pub const BOND_COUNT: Item<u64> = Item::new("bond_count");
+
+pub struct BondIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, BondingRecord, u64>,
+}
+
+impl<'a> IndexList<BondingRecord> for BondIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<BondingRecord>> + '_> {
+        let v: Vec<&dyn Index<BondingRecord>> = vec![&self.owner];
+        Box::new(v.into_iter())
     }
-    Ok(())
 }
 
-// Function to check if the sender is the owner of the contract
-pub fn check_owner(storage: &dyn Storage, address: Addr) -> Result<(), ContractError> {
-    let cfg = CONFIG.load(storage)?;
-    if cfg.owner != address {
-        return Err(ContractError::Unauthorized {});
+// BONDING stores bonding records by bond id, indexed by owner
+pub fn bonding<'a>() -> IndexedMap<'a, u64, BondingRecord, BondIndexes<'a>> {
+    let indexes = BondIndexes {
+        owner: MultiIndex::new(|d: &BondingRecord| d.owner.clone(), "bonding", "bonding__owner"),
+    };
+    IndexedMap::new("bonding", indexes)
+}
+
+// How a bond came about
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum BondKind {
+    Bond {},
+    // Liquidity added through the pool, which converts the deposit to Fury itself
+    LpBond {},
+    // Reward for referring a bond, nothing was paid in
+    Referral {},
+}
+
+impl Default for BondKind {
+    fn default() -> Self {
+        BondKind::Bond {}
+    }
+}
+
+// Accounting record of a bond, kept after the bond itself is paid out and pruned
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct BondHistoryEntry {
+    pub bond_id: u64,
+    // The address that bought the bond, later NFT holders are not tracked here
+    pub owner: Addr,
+    pub market_id: Option<u64>,
+    #[serde(default)]
+    pub kind: BondKind,
+    #[serde(default)]
+    pub tier: Option<u32>,
+    pub denom: Denom,
+    pub amount: Uint128,
+    // Unknown for LP bonds, the pool takes its fee before bonding
+    pub fee: Option<Uint128>,
+    // Input per Fury at the pool when bonding, before the discount, unknown for LP bonds
+    pub price: Option<Decimal>,
+    pub discount: u64,
+    pub payout: Uint128,
+    pub claimed: Uint128,
+    pub penalty: Uint128,
+    pub created_at: u64,
+    pub last_claimed_at: Option<u64>,
+    pub closed_at: Option<u64>,
+}
+
+impl BondHistoryEntry {
+    // Entry for a new bond with nothing claimed yet
+    pub fn new(bond_id: u64, owner: &Addr, market_id: Option<u64>, kind: BondKind, denom: Denom, amount: Uint128, now: u64) -> Self {
+        BondHistoryEntry {
+            bond_id,
+            owner: owner.clone(),
+            market_id,
+            kind,
+            tier: None,
+            denom,
+            amount,
+            fee: None,
+            price: None,
+            discount: 0,
+            payout: Uint128::zero(),
+            claimed: Uint128::zero(),
+            penalty: Uint128::zero(),
+            created_at: now,
+            last_claimed_at: None,
+            closed_at: None,
+        }
     }
-    Ok(())
 }
 
-// Enum to represent contract errors
-#[derive(Debug, PartialEq, Eq)]
-pub enum ContractError {
-    Disabled,
-    Unauthorized,
-    // Define additional contract errors if needed
+pub struct BondHistoryIndexes<'a> {
+    pub owner: MultiIndex<'a, Addr, BondHistoryEntry, u64>,
 }
 
-// Implement std::fmt::Display for ContractError
-impl std::fmt::Display for ContractError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+impl<'a> IndexList<BondHistoryEntry> for BondHistoryIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<BondHistoryEntry>> + '_> {
+        let v: Vec<&dyn Index<BondHistoryEntry>> = vec![&self.owner];
+        Box::new(v.into_iter())
     }
 }
 
-// Implement std::error::Error for ContractError
-impl std::error::Error for ContractError {}
+// BOND_HISTORY stores history entries by bond id, indexed by the buying owner
+pub fn bond_history<'a>() -> IndexedMap<'a, u64, BondHistoryEntry, BondHistoryIndexes<'a>> {
+    let indexes = BondHistoryIndexes {
+        owner: MultiIndex::new(|d: &BondHistoryEntry| d.owner.clone(), "bond_history", "bond_history__owner"),
+    };
+    IndexedMap::new("bond_history", indexes)
+}
+
+pub fn next_bond_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let id = BOND_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    BOND_COUNT.save(storage, &id)?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(amount: u128, claimed: u128, start_time: u64, timestamp: u64) -> BondingRecord {
+        BondingRecord {
+            id: 1,
+            owner: Addr::unchecked("owner"),
+            market_id: None,
+            tier: None,
+            amount: Uint128::new(amount),
+            claimed: Uint128::new(claimed),
+            start_time,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn vested_is_linear_over_the_term() {
+        let bond = record(1000, 0, 100, 1100);
+        assert_eq!(bond.vested(0), Uint128::zero());
+        assert_eq!(bond.vested(100), Uint128::zero());
+        assert_eq!(bond.vested(350), Uint128::new(250));
+        assert_eq!(bond.vested(600), Uint128::new(500));
+        assert_eq!(bond.vested(1100), Uint128::new(1000));
+        assert_eq!(bond.vested(5000), Uint128::new(1000));
+    }
+
+    #[test]
+    fn vested_without_a_term_is_immediate() {
+        let bond = record(1000, 0, 100, 100);
+        assert_eq!(bond.vested(100), Uint128::new(1000));
+        assert_eq!(bond.vested(50), Uint128::zero());
+    }
+
+    #[test]
+    fn claimable_excludes_claimed() {
+        let bond = record(1000, 300, 100, 1100);
+        assert_eq!(bond.claimable(350), Uint128::zero());
+        assert_eq!(bond.claimable(600), Uint128::new(200));
+        assert_eq!(bond.claimable(1100), Uint128::new(700));
+        assert!(!bond.is_fully_claimed());
+        assert!(record(1000, 1000, 100, 1100).is_fully_claimed());
+    }
+}
@@ -0,0 +1,82 @@
+use cosmwasm_std::{StdResult, Storage, Uint128};
+use cw20::Denom;
+
+use crate::state::{BondingRecord, DenomStats, MARKET_STATS, STATS, DENOM_STATS};
+
+fn denom_key(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(address) => address.to_string(),
+    }
+}
+
+// Counts a new bond paying `payout` Fury for `bonded` of `denom`, after `fee` was taken
+pub fn record_bond(
+    storage: &mut dyn Storage,
+    market_id: Option<u64>,
+    denom: &Denom,
+    bonded: Uint128,
+    fee: Uint128,
+    payout: Uint128,
+) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    stats.total_payout += payout;
+    stats.active_bonds += 1;
+    STATS.save(storage, &stats)?;
+
+    record_denom(storage, denom, bonded, fee)?;
+
+    if let Some(market_id) = market_id {
+        let mut market_stats = MARKET_STATS.may_load(storage, market_id)?.unwrap_or_default();
+        market_stats.bonded += bonded;
+        market_stats.payout += payout;
+        market_stats.active_bonds += 1;
+        MARKET_STATS.save(storage, market_id, &market_stats)?;
+    }
+    Ok(())
+}
+
+pub fn record_fee(storage: &mut dyn Storage, denom: &Denom, fee: Uint128) -> StdResult<()> {
+    record_denom(storage, denom, Uint128::zero(), fee)
+}
+
+fn record_denom(storage: &mut dyn Storage, denom: &Denom, bonded: Uint128, fee: Uint128) -> StdResult<()> {
+    let key = denom_key(denom);
+    let mut denom_stats = DENOM_STATS.may_load(storage, &key)?.unwrap_or(DenomStats {
+        denom: denom.clone(),
+        bonded: Uint128::zero(),
+        fees: Uint128::zero(),
+    });
+    denom_stats.bonded += bonded;
+    denom_stats.fees += fee;
+    DENOM_STATS.save(storage, &key, &denom_stats)
+}
+
+// Counts Fury released from a bond, `record` already reflecting the claim
+pub fn record_claim(
+    storage: &mut dyn Storage,
+    record: &BondingRecord,
+    claimed: Uint128,
+    penalty: Uint128,
+) -> StdResult<()> {
+    let closed = record.is_fully_claimed();
+
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    stats.total_claimed += claimed;
+    stats.total_penalty += penalty;
+    if closed {
+        stats.active_bonds = stats.active_bonds.saturating_sub(1);
+    }
+    STATS.save(storage, &stats)?;
+
+    if let Some(market_id) = record.market_id {
+        let mut market_stats = MARKET_STATS.may_load(storage, market_id)?.unwrap_or_default();
+        market_stats.claimed += claimed;
+        market_stats.penalty += penalty;
+        if closed {
+            market_stats.active_bonds = market_stats.active_bonds.saturating_sub(1);
+        }
+        MARKET_STATS.save(storage, market_id, &market_stats)?;
+    }
+    Ok(())
+}
@@ -1,21 +1,27 @@
 use cosmwasm_std::{
-    attr, entry_point, to_binary, Addr, Binary, BlockInfo, Coin, CosmosMsg, Deps, DepsMut, Env,
-    MessageInfo, Reply, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg, BankMsg, from_binary, ReplyOn
+    attr, entry_point, to_binary, Addr, Binary, BlockInfo, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
+    MessageInfo, Reply, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg, BankMsg, ReplyOn,
+    Order, Storage
 };
-use cw20::Cw20ReceiveMsg;
+use cw_storage_plus::Bound;
 use cw0::parse_reply_instantiate_data;
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw20::Denom::Cw20;
 use cw20::{Cw20ExecuteMsg, Denom, Expiration, MinterResponse};
 use cw20_base::contract::query_balance;
 
 use crate::error::ContractError;
 use crate::msg::{
-    ExecuteMsg, InfoResponse, InstantiateMsg, QueryMsg, Token1ForToken2PriceResponse,
-    Token2ForToken1PriceResponse, TokenSelect, StakeReceiveMsg
+    ExecuteMsg, InfoResponse, InstantiateMsg, MigrateMsg, QueryMsg, Token1ForToken2PriceResponse,
+    Token2ForToken1PriceResponse, TokenSelect, ReserveHistoryResponse,
+    DailyVolumeResponse, VolumeStatsResponse, ConfigResponse, PoolResponse,
+    SimulateProvideResponse, SimulateWithdrawResponse, PositionBond, PositionResponse, TwapResponse
 };
-use crate::state::{Token, LP_TOKEN, TOKEN1, TOKEN2, Config, CONFIG};
-use crate::util::{NORMAL_DECIMAL, THOUSAND};
+use crate::state::{
+    Token, LP_TOKEN, TOKEN1, TOKEN2, Config, CONFIG, LEGACY_CONFIG, ReserveSnapshot, RESERVE_HISTORY,
+    RESERVE_HISTORY_COUNT, MAX_RESERVE_SNAPSHOTS, DAILY_VOLUME, MAX_VOLUME_DAYS
+};
+use crate::util::THOUSAND;
 use crate::util;
 
 // Version info for migration info
@@ -24,7 +30,12 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const INSTANTIATE_LP_TOKEN_REPLY_ID: u64 = 0;
 const INSTANTIATE_BONDING_ID:u64 = 1;
-use fanfurybonding::msg::{InstantiateMsg as BondingInstantiateMsg, ExecuteMsg as BondingExecuteMsg};
+const DAY_SECONDS: u64 = 86400;
+use fanfurybonding::msg::{
+    InstantiateMsg as BondingInstantiateMsg, ExecuteMsg as BondingExecuteMsg,
+    QueryMsg as BondingQueryMsg, BondHistoryResponse
+};
+use fanfurybonding::state::{BondKind, PriceSource};
 
 
 // Note, you can use StdResult in some functions where you do not
@@ -47,9 +58,10 @@ pub fn instantiate(
         usdc_denom: msg.usdc_denom.clone(),
         tx_fee: msg.tx_fee,
         platform_fee: msg.platform_fee,
-        lock_days: msg.lock_days,
+        term: msg.term,
         discount: msg.discount
     };
+    validate_fees(&config)?;
     CONFIG.save(deps.storage, &config)?;
 
     let token1 = Token {
@@ -103,8 +115,22 @@ pub fn execute(
         ExecuteMsg::UpdateConfig {
             owner,
             bonding_contract_address,
-            treasury_address
-        } => execute_update_config(info, deps, owner, bonding_contract_address, treasury_address),
+            treasury_address,
+            tx_fee,
+            platform_fee,
+            term,
+            discount,
+        } => execute_update_config(
+            info,
+            deps,
+            owner,
+            bonding_contract_address,
+            treasury_address,
+            tx_fee,
+            platform_fee,
+            term,
+            discount,
+        ),
         ExecuteMsg::AddLiquidity {
             token1_amount,
             min_liquidity,
@@ -151,26 +177,60 @@ pub fn execute(
 
 
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_update_config(
     info: MessageInfo,
     deps: DepsMut,
-    owner: Addr,
-    bonding_contract_address: Addr,
-    treasury_address: Addr
+    owner: Option<Addr>,
+    bonding_contract_address: Option<Addr>,
+    treasury_address: Option<Addr>,
+    tx_fee: Option<u64>,
+    platform_fee: Option<u64>,
+    term: Option<u64>,
+    discount: Option<u64>,
 ) -> Result<Response, ContractError> {
     let mut config: Config = CONFIG.load(deps.storage)?;
 
     if info.sender.clone() != config.owner {
         return Err(ContractError::Unauthorized {});
     };
-    
-    config.owner = owner;
-    config.bonding_contract_address = bonding_contract_address;
-    config.treasury_address = treasury_address;
+
+    if let Some(owner) = owner {
+        config.owner = owner;
+    }
+    if let Some(bonding_contract_address) = bonding_contract_address {
+        config.bonding_contract_address = bonding_contract_address;
+    }
+    if let Some(treasury_address) = treasury_address {
+        config.treasury_address = treasury_address;
+    }
+
+    let bonding_changed = tx_fee.is_some() || platform_fee.is_some() || term.is_some() || discount.is_some();
+    config.tx_fee = tx_fee.unwrap_or(config.tx_fee);
+    config.platform_fee = platform_fee.unwrap_or(config.platform_fee);
+    config.term = term.unwrap_or(config.term);
+    config.discount = discount.unwrap_or(config.discount);
+    validate_fees(&config)?;
 
     CONFIG.save(deps.storage, &config)?;
 
+    // keep the linked bonding contract in sync
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if bonding_changed {
+        messages.push(WasmMsg::Execute {
+            contract_addr: config.bonding_contract_address.to_string(),
+            msg: to_binary(&BondingExecuteMsg::UpdateConfig {
+                term: config.term,
+                discount: config.discount,
+                tx_fee: config.tx_fee,
+                platform_fee: config.platform_fee,
+            })?,
+            funds: vec![],
+        }.into());
+    }
+
     Ok(Response::new()
+        .add_messages(messages)
         .add_attribute("action", "update_config")
         .add_attribute(
             "owner",
@@ -179,11 +239,27 @@ pub fn execute_update_config(
                 .to_string(),
         )
         .add_attribute(
-            "staking_address",
+            "bonding_address",
             config
-                .bonding_code_id
+                .bonding_contract_address
                 .to_string(),
-        ))
+        )
+        .add_attribute("tx_fee", config.tx_fee.to_string())
+        .add_attribute("platform_fee", config.platform_fee.to_string())
+        .add_attribute("term", config.term.to_string())
+        .add_attribute("discount", config.discount.to_string()))
+}
+
+// fees and discount are per mille and are subtracted from THOUSAND when bonding
+fn validate_fees(config: &Config) -> Result<(), ContractError> {
+    let total = config
+        .tx_fee
+        .checked_add(config.platform_fee)
+        .and_then(|fees| fees.checked_add(config.discount));
+    match total {
+        Some(total) if total < THOUSAND => Ok(()),
+        _ => Err(ContractError::InvalidFeeConfig { max: THOUSAND }),
+    }
 }
 
 fn check_expiration(
@@ -205,7 +281,7 @@ fn get_lp_token_amount_to_mint(
     token1_amount: Uint128,
     liquidity_supply: Uint128,
     token1_reserve: Uint128,
-) -> Result<Uint128, ContractError> {
+) -> Result<Uint128, StdError> {
     if liquidity_supply == Uint128::zero() {
         Ok(token1_amount)
     } else {
@@ -237,6 +313,34 @@ fn get_token2_amount_required(
     }
 }
 
+// Platform and tx fee charged on AddLiquidity, doubled because the bond is twice token2
+fn get_add_liquidity_min_fee(cfg: &Config, token1_amount: Uint128) -> Uint128 {
+    token1_amount * Uint128::from(cfg.platform_fee + cfg.tx_fee) * Uint128::from(2u128) / Uint128::from(THOUSAND)
+}
+
+fn get_bond_amount(token2_amount: Uint128) -> Uint128 {
+    token2_amount * Uint128::from(2u128)
+}
+
+fn get_withdraw_amounts(
+    amount: Uint128,
+    lp_token_supply: Uint128,
+    token1_reserve: Uint128,
+    token2_reserve: Uint128,
+) -> Result<(Uint128, Uint128), StdError> {
+    let token1_amount = amount
+        .checked_mul(token1_reserve)
+        .map_err(StdError::overflow)?
+        .checked_div(lp_token_supply)
+        .map_err(StdError::divide_by_zero)?;
+    let token2_amount = amount
+        .checked_mul(token2_reserve)
+        .map_err(StdError::overflow)?
+        .checked_div(lp_token_supply)
+        .map_err(StdError::divide_by_zero)?;
+    Ok((token1_amount, token2_amount))
+}
+
 pub fn execute_add_liquidity(
     deps: DepsMut,
     info: &MessageInfo,
@@ -321,30 +425,46 @@ pub fn execute_add_liquidity(
         token2.reserve += token2_amount;
         Ok(token2)
     })?;
+    record_reserve_snapshot(deps.storage, &env.block)?;
+
+    let config = CONFIG.load(deps.storage)?;
+
+    // Liquidity added by the bonding contract is protocol owned, its LP goes to the treasury without a fee or bond
+    if info.sender == config.bonding_contract_address {
+        let mint_msg = mint_lp_tokens(&config.treasury_address, liquidity_amount, &lp_token_addr)?;
+        return Ok(Response::new()
+            .add_messages(transfer_msgs)
+            .add_message(mint_msg)
+            .add_attributes(vec![
+                attr("action", "add_protocol_liquidity"),
+                attr("token1_amount", token1_amount),
+                attr("token2_amount", token2_amount),
+                attr("liquidity_received", liquidity_amount),
+            ]));
+    }
 
-    let mut config = CONFIG.load(deps.storage)?;
     // let mint_msg = mint_lp_tokens(&info.sender, liquidity_amount, &lp_token_addr)?;
     let mint_msg = mint_lp_tokens(&config.owner, liquidity_amount, &lp_token_addr)?;
 
-    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    ///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-    /// Bonding Part
+    // Bonding Part
     
     // Do staking with double amount of token2
     let mut bond_msgs:Vec<CosmosMsg> = vec![];
-    let bond_amount = token2_amount * Uint128::from(2u128);
+    let bond_amount = get_bond_amount(token2_amount);
 
     // check if the fee is larger than required
-    if fee_amount < token1_amount * Uint128::from(config.platform_fee + config.tx_fee) * Uint128::from(2u128) / Uint128::from(THOUSAND) {
+    if fee_amount < get_add_liquidity_min_fee(&config, token1_amount) {
         return Err(ContractError::InsufficientFee {  })
     }
 
     bond_msgs.push(util::transfer_token_message(token1.clone().denom.clone(), fee_amount, config.treasury_address.clone())?);
     
     // make bonding
-    let bond_msg = BondingExecuteMsg::LpBond { address: info.sender.clone(), amount: bond_amount };
+    let bond_msg = BondingExecuteMsg::LpBond {
+        address: info.sender.clone(),
+        amount: bond_amount,
+        lp_amount: liquidity_amount,
+    };
     
     bond_msgs.push(WasmMsg::Execute {
         contract_addr: config.bonding_contract_address.into(),
@@ -441,6 +561,7 @@ fn get_cw20_transfer_from_msg(
     Ok(cw20_transfer_cosmos_msg)
 }
 
+#[allow(dead_code)]
 fn get_cw20_increase_allowance_msg(
     token_addr: &Addr,
     spender: &Addr,
@@ -485,11 +606,8 @@ pub fn execute_remove_liquidity(
         });
     }
 
-    let token1_amount = amount
-        .checked_mul(token1.reserve)
-        .map_err(StdError::overflow)?
-        .checked_div(lp_token_supply)
-        .map_err(StdError::divide_by_zero)?;
+    let (token1_amount, token2_amount) =
+        get_withdraw_amounts(amount, lp_token_supply, token1.reserve, token2.reserve)?;
     if token1_amount < min_token1 {
         return Err(ContractError::MinToken1Error {
             requested: min_token1,
@@ -497,11 +615,6 @@ pub fn execute_remove_liquidity(
         });
     }
 
-    let token2_amount = amount
-        .checked_mul(token2.reserve)
-        .map_err(StdError::overflow)?
-        .checked_div(lp_token_supply)
-        .map_err(StdError::divide_by_zero)?;
     if token2_amount < min_token2 {
         return Err(ContractError::MinToken2Error {
             requested: min_token2,
@@ -524,6 +637,7 @@ pub fn execute_remove_liquidity(
             .map_err(StdError::overflow)?;
         Ok(token2)
     })?;
+    record_reserve_snapshot(deps.storage, &env.block)?;
 
     let token1_transfer_msg = match token1.denom {
         Denom::Cw20(addr) => get_cw20_transfer_to_msg(&info.sender, &addr, token1_amount)?,
@@ -740,6 +854,9 @@ pub fn execute_swap(
         },
     )?;
 
+    record_volume(deps.storage, &_env.block, &input_token_enum, input_amount, token_bought, fee_amount)?;
+    record_reserve_snapshot(deps.storage, &_env.block)?;
+
     Ok(Response::new()
         .add_messages(transfer_msgs)
         .add_attributes(vec![
@@ -748,18 +865,114 @@ pub fn execute_swap(
         ]))
 }
 
+// Stores the reserves at the end of the current block, dropping the oldest snapshot when full
+fn record_reserve_snapshot(storage: &mut dyn Storage, block: &BlockInfo) -> StdResult<()> {
+    let token1 = TOKEN1.load(storage)?;
+    let token2 = TOKEN2.load(storage)?;
+    let is_new_block = !RESERVE_HISTORY.has(storage, block.height);
+
+    RESERVE_HISTORY.save(
+        storage,
+        block.height,
+        &ReserveSnapshot {
+            height: block.height,
+            time: block.time.seconds(),
+            token1_reserve: token1.reserve,
+            token2_reserve: token2.reserve,
+        },
+    )?;
+
+    if is_new_block {
+        let mut count = RESERVE_HISTORY_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+        if count > MAX_RESERVE_SNAPSHOTS {
+            let oldest = RESERVE_HISTORY
+                .keys(storage, None, None, Order::Ascending)
+                .next()
+                .transpose()?;
+            if let Some(height) = oldest {
+                RESERVE_HISTORY.remove(storage, height);
+                count -= 1;
+            }
+        }
+        RESERVE_HISTORY_COUNT.save(storage, &count)?;
+    }
+    Ok(())
+}
+
+// Adds a trade to today's counters and drops days that fell out of the window
+fn record_volume(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    input_token: &TokenSelect,
+    input_amount: Uint128,
+    output_amount: Uint128,
+    fee_amount: Uint128,
+) -> StdResult<()> {
+    let day = block.time.seconds() / DAY_SECONDS;
+
+    DAILY_VOLUME.update(storage, day, |volume| -> StdResult<_> {
+        let mut volume = volume.unwrap_or_default();
+        match input_token {
+            TokenSelect::Token1 => {
+                volume.token1_volume = volume.token1_volume.checked_add(input_amount)?;
+                volume.token2_volume = volume.token2_volume.checked_add(output_amount)?;
+                volume.fees_token1_sold = volume.fees_token1_sold.checked_add(fee_amount)?;
+            }
+            TokenSelect::Token2 => {
+                volume.token2_volume = volume.token2_volume.checked_add(input_amount)?;
+                volume.token1_volume = volume.token1_volume.checked_add(output_amount)?;
+                volume.fees_token2_sold = volume.fees_token2_sold.checked_add(fee_amount)?;
+            }
+        }
+        volume.trade_count += 1;
+        Ok(volume)
+    })?;
+
+    if day >= MAX_VOLUME_DAYS {
+        let expired: Vec<u64> = DAILY_VOLUME
+            .keys(
+                storage,
+                None,
+                Some(Bound::exclusive(day + 1 - MAX_VOLUME_DAYS)),
+                Order::Ascending,
+            )
+            .collect::<StdResult<_>>()?;
+        for day in expired {
+            DAILY_VOLUME.remove(storage, day);
+        }
+    }
+    Ok(())
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Balance { address } => to_binary(&query_balance(deps, address)?),
         QueryMsg::Info {} => to_binary(&query_info(deps)?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Pool {} => to_binary(&query_pool(deps)?),
         QueryMsg::Token1ForToken2Price { token1_amount } => {
             to_binary(&query_token1_for_token2_price(deps, token1_amount)?)
         }
         QueryMsg::Token2ForToken1Price { token2_amount } => {
             to_binary(&query_token2_for_token1_price(deps, token2_amount)?)
         }
+        QueryMsg::SimulateProvide { token1_amount, max_token2 } => {
+            to_binary(&query_simulate_provide(deps, token1_amount, max_token2)?)
+        }
+        QueryMsg::SimulateWithdraw { amount } => {
+            to_binary(&query_simulate_withdraw(deps, amount)?)
+        }
+        QueryMsg::Position { address, start_after, limit } => {
+            to_binary(&query_position(deps, address, start_after, limit)?)
+        }
+        QueryMsg::Twap { window } => to_binary(&query_twap(deps, env, window)?),
+        QueryMsg::ReserveHistory { start_after, limit } => {
+            to_binary(&query_reserve_history(deps, start_after, limit)?)
+        }
+        QueryMsg::VolumeStats { start_after, limit } => {
+            to_binary(&query_volume_stats(deps, start_after, limit)?)
+        }
     }
 }
 
@@ -778,6 +991,185 @@ pub fn query_info(deps: Deps) -> StdResult<InfoResponse> {
     })
 }
 
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: cfg.owner,
+        bonding_code_id: cfg.bonding_code_id,
+        bonding_contract_address: cfg.bonding_contract_address,
+        fury_token_address: cfg.fury_token_address,
+        treasury_address: cfg.treasury_address,
+        usdc_denom: cfg.usdc_denom,
+        tx_fee: cfg.tx_fee,
+        platform_fee: cfg.platform_fee,
+        term: cfg.term,
+        discount: cfg.discount,
+    })
+}
+
+pub fn query_pool(deps: Deps) -> StdResult<PoolResponse> {
+    Ok(PoolResponse {
+        token1: TOKEN1.load(deps.storage)?,
+        token2: TOKEN2.load(deps.storage)?,
+        lp_token_address: LP_TOKEN.load(deps.storage)?,
+    })
+}
+
+pub fn query_simulate_provide(
+    deps: Deps,
+    token1_amount: Uint128,
+    max_token2: Uint128,
+) -> StdResult<SimulateProvideResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let token1 = TOKEN1.load(deps.storage)?;
+    let token2 = TOKEN2.load(deps.storage)?;
+    let lp_token_addr = LP_TOKEN.load(deps.storage)?;
+
+    let lp_token_supply = get_lp_token_supply(deps, &lp_token_addr)?;
+    let liquidity_amount =
+        get_lp_token_amount_to_mint(token1_amount, lp_token_supply, token1.reserve)?;
+    let token2_amount = get_token2_amount_required(
+        max_token2,
+        token1_amount,
+        lp_token_supply,
+        token2.reserve,
+        token1.reserve,
+    )?;
+
+    Ok(SimulateProvideResponse {
+        token2_amount,
+        liquidity_amount,
+        min_fee_amount: get_add_liquidity_min_fee(&cfg, token1_amount),
+        bond_amount: get_bond_amount(token2_amount),
+    })
+}
+
+pub fn query_simulate_withdraw(deps: Deps, amount: Uint128) -> StdResult<SimulateWithdrawResponse> {
+    let token1 = TOKEN1.load(deps.storage)?;
+    let token2 = TOKEN2.load(deps.storage)?;
+    let lp_token_addr = LP_TOKEN.load(deps.storage)?;
+
+    let lp_token_supply = get_lp_token_supply(deps, &lp_token_addr)?;
+    let (token1_amount, token2_amount) =
+        get_withdraw_amounts(amount, lp_token_supply, token1.reserve, token2.reserve)?;
+
+    Ok(SimulateWithdrawResponse {
+        token1_amount,
+        token2_amount,
+    })
+}
+
+pub fn query_position(
+    deps: Deps,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PositionResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let token1 = TOKEN1.load(deps.storage)?;
+    let token2 = TOKEN2.load(deps.storage)?;
+    let lp_token_addr = LP_TOKEN.load(deps.storage)?;
+
+    let lp_balance = get_token_balance(deps, &lp_token_addr, &address)?;
+    let lp_token_supply = get_lp_token_supply(deps, &lp_token_addr)?;
+
+    let (pool_share, token1_amount, token2_amount) = if lp_token_supply.is_zero() {
+        (Decimal::zero(), Uint128::zero(), Uint128::zero())
+    } else {
+        let (token1_amount, token2_amount) =
+            get_withdraw_amounts(lp_balance, lp_token_supply, token1.reserve, token2.reserve)?;
+        (
+            Decimal::from_ratio(lp_balance, lp_token_supply),
+            token1_amount,
+            token2_amount,
+        )
+    };
+
+    // a single page of the owner's bond history, only the LP bonds come from this pool
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let history: BondHistoryResponse = deps.querier.query_wasm_smart(
+        &cfg.bonding_contract_address,
+        &BondingQueryMsg::BondHistory {
+            address: address.clone(),
+            start_time: None,
+            end_time: None,
+            start_after,
+            limit: Some(limit),
+        },
+    )?;
+    // a short page means the history was read to the end
+    let next_start_after = if history.list.len() < limit as usize {
+        None
+    } else {
+        history.list.last().map(|entry| entry.bond_id)
+    };
+    let bonds = history
+        .list
+        .into_iter()
+        .filter(|entry| entry.kind == BondKind::LpBond {})
+        .map(|entry| PositionBond {
+            bond_id: entry.bond_id,
+            amount: entry.amount,
+            payout: entry.payout,
+            claimed: entry.claimed,
+            created_at: entry.created_at,
+            closed_at: entry.closed_at,
+        })
+        .collect();
+
+    Ok(PositionResponse {
+        address,
+        lp_holder: cfg.owner,
+        lp_balance,
+        pool_share,
+        token1_amount,
+        token2_amount,
+        bonds,
+        next_start_after,
+    })
+}
+
+pub fn query_twap(deps: Deps, env: Env, window: u64) -> StdResult<TwapResponse> {
+    let now = env.block.time.seconds();
+    let window_start = now.saturating_sub(window);
+
+    // walk back from the newest snapshot, each price holds until the next snapshot
+    let mut weighted = Decimal::zero();
+    let mut period_end = now;
+    for item in RESERVE_HISTORY.range(deps.storage, None, None, Order::Descending) {
+        let (_, snapshot) = item?;
+        if snapshot.token2_reserve.is_zero() {
+            break;
+        }
+        let period_start = snapshot.time.max(window_start);
+        let price = Decimal::from_ratio(snapshot.token1_reserve, snapshot.token2_reserve);
+        weighted += price * Decimal::from_ratio(period_end - period_start, 1u64);
+        period_end = period_start;
+        if snapshot.time <= window_start {
+            break;
+        }
+    }
+
+    let duration = now - period_end;
+    let price = if duration == 0 {
+        let token1 = TOKEN1.load(deps.storage)?;
+        let token2 = TOKEN2.load(deps.storage)?;
+        if token2.reserve.is_zero() {
+            return Err(StdError::generic_err("No liquidity"));
+        }
+        Decimal::from_ratio(token1.reserve, token2.reserve)
+    } else {
+        weighted / Uint128::from(duration)
+    };
+
+    Ok(TwapResponse {
+        price,
+        start_time: period_end,
+        end_time: now,
+    })
+}
+
 pub fn query_token1_for_token2_price(
     deps: Deps,
     token1_amount: Uint128,
@@ -798,6 +1190,55 @@ pub fn query_token2_for_token1_price(
     Ok(Token2ForToken1PriceResponse { token1_amount })
 }
 
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+pub fn query_reserve_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ReserveHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let snapshots: StdResult<Vec<_>> = RESERVE_HISTORY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect();
+
+    Ok(ReserveHistoryResponse {
+        snapshots: snapshots?,
+    })
+}
+
+pub fn query_volume_stats(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<VolumeStatsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let days: StdResult<Vec<_>> = DAILY_VOLUME
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(day, volume)| DailyVolumeResponse {
+                day,
+                token1_volume: volume.token1_volume,
+                token2_volume: volume.token2_volume,
+                fees_token1_sold: volume.fees_token1_sold,
+                fees_token2_sold: volume.fees_token2_sold,
+                trade_count: volume.trade_count,
+            })
+        })
+        .collect();
+
+    Ok(VolumeStatsResponse { days: days? })
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
     if msg.id != INSTANTIATE_LP_TOKEN_REPLY_ID {
@@ -816,9 +1257,7 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractEr
                 //Instantiate bonding contract
 
                 let cfg = CONFIG.load(deps.storage)?;
-                let mut sub_msg: Vec<SubMsg> = vec![];
-
-                sub_msg.push(SubMsg {
+                let sub_msg: Vec<SubMsg> = vec![SubMsg {
                     msg: WasmMsg::Instantiate {
                         code_id: cfg.bonding_code_id,
                         funds: vec![],
@@ -829,18 +1268,24 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractEr
                             pool_address: env.contract.address.clone(),
                             treasury_address: cfg.treasury_address.clone(),
                             fury_token_address: cfg.fury_token_address.clone(),
-                            lock_days: cfg.lock_days,
+                            term: cfg.term,
                             discount: cfg.discount,
                             usdc_denom: cfg.usdc_denom,
                             is_native_bonding: false,
                             tx_fee: cfg.tx_fee,
-                            platform_fee: cfg.platform_fee
+                            platform_fee: cfg.platform_fee,
+                            price_source: PriceSource::PoolSpot {},
+                            max_price_age: 0,
+                            max_price_deviation: 0,
+                            control_variable: 0,
+                            min_price: Decimal::zero(),
+                            max_debt: None,
                         })?,
                     }.into(),
                     id: INSTANTIATE_BONDING_ID,
                     gas_limit: None,
                     reply_on: ReplyOn::Success,
-                });
+                }];
 
                 Ok(Response::new().add_submessages(sub_msg))
             } else if msg.id == INSTANTIATE_BONDING_ID {
@@ -858,3 +1303,151 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractEr
         Err(_) => Err(ContractError::InstantiateLpTokenError {}),
     }
 }
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: version.contract,
+        });
+    }
+
+    // pools deployed with lock_days keep the same term, counted in seconds
+    if CONFIG.load(deps.storage).is_err() {
+        let legacy = LEGACY_CONFIG.load(deps.storage)?;
+        CONFIG.save(
+            deps.storage,
+            &Config {
+                owner: legacy.owner,
+                bonding_code_id: legacy.bonding_code_id,
+                bonding_contract_address: legacy.bonding_contract_address,
+                fury_token_address: legacy.fury_token_address,
+                treasury_address: legacy.treasury_address,
+                usdc_denom: legacy.usdc_denom,
+                tx_fee: legacy.tx_fee,
+                platform_fee: legacy.platform_fee,
+                term: legacy.lock_days * DAY_SECONDS,
+                discount: legacy.discount,
+            },
+        )?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new().add_attribute("action", "migrate"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::Timestamp;
+
+    fn set_reserves(storage: &mut dyn Storage, token1_reserve: u128, token2_reserve: u128) {
+        TOKEN1
+            .save(storage, &Token { reserve: Uint128::new(token1_reserve), denom: Denom::Native("uusdc".to_string()) })
+            .unwrap();
+        TOKEN2
+            .save(storage, &Token { reserve: Uint128::new(token2_reserve), denom: Cw20(Addr::unchecked("fury")) })
+            .unwrap();
+    }
+
+    fn block(height: u64, time: u64) -> BlockInfo {
+        BlockInfo {
+            height,
+            time: Timestamp::from_seconds(time),
+            chain_id: "testing".to_string(),
+        }
+    }
+
+    fn env_at(time: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(time);
+        env
+    }
+
+    #[test]
+    fn reserve_snapshots_evict_oldest() {
+        let mut deps = mock_dependencies();
+        set_reserves(deps.as_mut().storage, 100, 100);
+
+        for height in 1..=MAX_RESERVE_SNAPSHOTS {
+            record_reserve_snapshot(deps.as_mut().storage, &block(height, height * 5)).unwrap();
+        }
+        assert_eq!(RESERVE_HISTORY_COUNT.load(deps.as_ref().storage).unwrap(), MAX_RESERVE_SNAPSHOTS);
+        assert!(RESERVE_HISTORY.has(deps.as_ref().storage, 1));
+
+        // a second trade in the same block overwrites its snapshot
+        set_reserves(deps.as_mut().storage, 150, 100);
+        record_reserve_snapshot(deps.as_mut().storage, &block(MAX_RESERVE_SNAPSHOTS, MAX_RESERVE_SNAPSHOTS * 5)).unwrap();
+        assert_eq!(RESERVE_HISTORY_COUNT.load(deps.as_ref().storage).unwrap(), MAX_RESERVE_SNAPSHOTS);
+        assert_eq!(
+            RESERVE_HISTORY.load(deps.as_ref().storage, MAX_RESERVE_SNAPSHOTS).unwrap().token1_reserve,
+            Uint128::new(150)
+        );
+
+        // a new block pushes the oldest snapshot out
+        let height = MAX_RESERVE_SNAPSHOTS + 1;
+        record_reserve_snapshot(deps.as_mut().storage, &block(height, height * 5)).unwrap();
+        assert_eq!(RESERVE_HISTORY_COUNT.load(deps.as_ref().storage).unwrap(), MAX_RESERVE_SNAPSHOTS);
+        assert!(!RESERVE_HISTORY.has(deps.as_ref().storage, 1));
+        assert!(RESERVE_HISTORY.has(deps.as_ref().storage, 2));
+        assert!(RESERVE_HISTORY.has(deps.as_ref().storage, height));
+    }
+
+    #[test]
+    fn twap_weights_prices_by_time() {
+        let mut deps = mock_dependencies();
+        set_reserves(deps.as_mut().storage, 100, 100);
+        record_reserve_snapshot(deps.as_mut().storage, &block(1, 1000)).unwrap();
+        set_reserves(deps.as_mut().storage, 200, 100);
+        record_reserve_snapshot(deps.as_mut().storage, &block(2, 2000)).unwrap();
+
+        // a price of 1 for 1000 seconds, then 2 for 1000 seconds
+        let twap = query_twap(deps.as_ref(), env_at(3000), 2000).unwrap();
+        assert_eq!(twap, TwapResponse { price: Decimal::percent(150), start_time: 1000, end_time: 3000 });
+
+        // only the newest price falls in a short window
+        let twap = query_twap(deps.as_ref(), env_at(3000), 500).unwrap();
+        assert_eq!(twap, TwapResponse { price: Decimal::percent(200), start_time: 2500, end_time: 3000 });
+
+        // history not reaching back over the window shows in start_time
+        let twap = query_twap(deps.as_ref(), env_at(3000), 5000).unwrap();
+        assert_eq!(twap, TwapResponse { price: Decimal::percent(150), start_time: 1000, end_time: 3000 });
+
+        // an empty window falls back to the spot price
+        set_reserves(deps.as_mut().storage, 300, 100);
+        let twap = query_twap(deps.as_ref(), env_at(3000), 0).unwrap();
+        assert_eq!(twap, TwapResponse { price: Decimal::percent(300), start_time: 3000, end_time: 3000 });
+    }
+
+    #[test]
+    fn migrate_lock_days_to_term() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        LEGACY_CONFIG
+            .save(
+                deps.as_mut().storage,
+                &crate::state::LegacyConfig {
+                    owner: Addr::unchecked("owner"),
+                    bonding_code_id: 1,
+                    bonding_contract_address: Addr::unchecked("bonding"),
+                    fury_token_address: Addr::unchecked("fury"),
+                    treasury_address: Addr::unchecked("treasury"),
+                    usdc_denom: "uusdc".to_string(),
+                    tx_fee: 10,
+                    platform_fee: 10,
+                    lock_days: 7,
+                    discount: 50,
+                },
+            )
+            .unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(cfg.term, 7 * DAY_SECONDS);
+        assert_eq!(cfg.bonding_contract_address, Addr::unchecked("bonding"));
+        assert_eq!(get_contract_version(deps.as_ref().storage).unwrap().version, CONTRACT_VERSION);
+    }
+}
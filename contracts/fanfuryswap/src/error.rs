@@ -86,4 +86,10 @@ pub enum ContractError {
 
     #[error("Disabled")]
     Disabled {},
+
+    #[error("Invalid config: tx_fee + platform_fee + discount must be below {max}")]
+    InvalidFeeConfig { max: u64 },
+
+    #[error("Cannot migrate from different contract type: {previous_contract}")]
+    CannotMigrate { previous_contract: String },
 }
@@ -1,5 +1,10 @@
+// handlers end in an explicit return and take their message fields as arguments,
+// to_binary and from_binary are kept for cosmwasm-std 1.0
+#![allow(clippy::needless_return, clippy::too_many_arguments, deprecated)]
+
 pub mod contract;
 pub mod error;
 pub mod msg;
 pub mod state;
-pub mod util;
\ No newline at end of file
+#[path = "../../../util/util.rs"]
+pub mod util;
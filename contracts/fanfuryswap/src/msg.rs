@@ -1,10 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cw20::Cw20ReceiveMsg;
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, Uint128};
 
 use cw20::{Denom, Expiration};
 
+use crate::state::{ReserveSnapshot, Token};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub lp_token_code_id: u64,
@@ -15,7 +19,8 @@ pub struct InstantiateMsg {
     pub usdc_denom: String,
     pub tx_fee: u64,
     pub platform_fee: u64,
-    pub lock_days: u64,
+    /// Bond vesting term in seconds, forwarded to the bonding contract.
+    pub term: u64,
     pub discount: u64,
 }
 
@@ -40,10 +45,15 @@ pub enum TokenSelect {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
+    /// Fee and bonding parameters are forwarded to the bonding contract when changed.
     UpdateConfig {
-        owner: Addr,
-        bonding_contract_address: Addr,
-        treasury_address: Addr
+        owner: Option<Addr>,
+        bonding_contract_address: Option<Addr>,
+        treasury_address: Option<Addr>,
+        tx_fee: Option<u64>,
+        platform_fee: Option<u64>,
+        term: Option<u64>,
+        discount: Option<u64>,
     },
     AddLiquidity {
         token1_amount: Uint128,
@@ -76,12 +86,45 @@ pub enum QueryMsg {
         address: String,
     },
     Info {},
+    /// Returns the full pool configuration.
+    Config {},
+    /// Returns both pool tokens with their reserves and the LP token address.
+    Pool {},
     Token1ForToken2Price {
         token1_amount: Uint128,
     },
     Token2ForToken1Price {
         token2_amount: Uint128,
     },
+    /// Simulates AddLiquidity with the current reserves and LP supply.
+    SimulateProvide {
+        token1_amount: Uint128,
+        max_token2: Uint128,
+    },
+    /// Simulates RemoveLiquidity with the current reserves and LP supply.
+    SimulateWithdraw {
+        amount: Uint128,
+    },
+    /// LP balance, pool share and one page of the LP bonds created through AddLiquidity.
+    Position {
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Time weighted token1 per token2 price over the last `window` seconds of reserve history.
+    Twap {
+        window: u64,
+    },
+    /// Reserve snapshots ordered by block height, oldest first.
+    ReserveHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Daily volume and fee counters ordered by day index, oldest first.
+    VolumeStats {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -94,6 +137,27 @@ pub struct InfoResponse {
     pub lp_token_address: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub bonding_code_id: u64,
+    pub bonding_contract_address: Addr,
+    pub fury_token_address: Addr,
+    pub treasury_address: Addr,
+    pub usdc_denom: String,
+    pub tx_fee: u64,
+    pub platform_fee: u64,
+    pub term: u64,
+    pub discount: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolResponse {
+    pub token1: Token,
+    pub token2: Token,
+    pub lp_token_address: Addr,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Token1ForToken2PriceResponse {
     pub token2_amount: Uint128,
@@ -103,3 +167,75 @@ pub struct Token1ForToken2PriceResponse {
 pub struct Token2ForToken1PriceResponse {
     pub token1_amount: Uint128,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateProvideResponse {
+    pub token2_amount: Uint128,
+    pub liquidity_amount: Uint128,
+    pub min_fee_amount: Uint128,
+    pub bond_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateWithdrawResponse {
+    pub token1_amount: Uint128,
+    pub token2_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionBond {
+    pub bond_id: u64,
+    /// Amount the pool bonded on the depositor's behalf.
+    pub amount: Uint128,
+    /// Fury the bond pays out over its term.
+    pub payout: Uint128,
+    pub claimed: Uint128,
+    pub created_at: u64,
+    pub closed_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PositionResponse {
+    pub address: Addr,
+    /// AddLiquidity mints LP to this owner, so deposits made through the pool show up as bonds
+    /// and the LP figures below only cover LP `address` holds itself.
+    pub lp_holder: Addr,
+    pub lp_balance: Uint128,
+    pub pool_share: Decimal,
+    pub token1_amount: Uint128,
+    pub token2_amount: Uint128,
+    pub bonds: Vec<PositionBond>,
+    /// Last bond id read from the bonding contract, `None` once every bond was read.
+    pub next_start_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TwapResponse {
+    pub price: Decimal,
+    /// Start of the period actually covered by the reserve history
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveHistoryResponse {
+    pub snapshots: Vec<ReserveSnapshot>,
+}
+
+/// Volume of one day, both fee counters are in token1 (usdc).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DailyVolumeResponse {
+    pub day: u64,
+    pub token1_volume: Uint128,
+    pub token2_volume: Uint128,
+    /// Fees in token1 taken on swaps selling token1.
+    pub fees_token1_sold: Uint128,
+    /// Fees in token1 taken on swaps selling token2.
+    pub fees_token2_sold: Uint128,
+    pub trade_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VolumeStatsResponse {
+    pub days: Vec<DailyVolumeResponse>,
+}
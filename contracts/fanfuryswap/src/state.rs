@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Addr, Uint128};
 use cw20::Denom;
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
 
 pub const LP_TOKEN: Item<Addr> = Item::new("lp_token");
 
@@ -19,6 +19,23 @@ pub const TOKEN2: Item<Token> = Item::new("token2");
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
+    pub owner: Addr,
+    pub bonding_code_id: u64,
+    pub bonding_contract_address: Addr,
+    pub fury_token_address: Addr,
+    pub treasury_address: Addr,
+    pub usdc_denom: String,
+    pub tx_fee: u64,
+    pub platform_fee: u64,
+    pub term: u64,
+    pub discount: u64,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+// Config as stored while the bond term was still counted in days, read by migrate
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyConfig {
     pub owner: Addr,
     pub bonding_code_id: u64,
     pub bonding_contract_address: Addr,
@@ -31,4 +48,36 @@ pub struct Config {
     pub discount: u64,
 }
 
-pub const CONFIG: Item<Config> = Item::new("config");
\ No newline at end of file
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+
+// Reserve snapshots are kept for the last MAX_RESERVE_SNAPSHOTS blocks that touched the pool
+pub const MAX_RESERVE_SNAPSHOTS: u64 = 1000;
+// Daily volume counters are kept for the last MAX_VOLUME_DAYS days
+pub const MAX_VOLUME_DAYS: u64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveSnapshot {
+    pub height: u64,
+    pub time: u64,
+    pub token1_reserve: Uint128,
+    pub token2_reserve: Uint128,
+}
+
+// Keyed by block height, the oldest entry is dropped once the buffer is full
+pub const RESERVE_HISTORY: Map<u64, ReserveSnapshot> = Map::new("reserve_history");
+pub const RESERVE_HISTORY_COUNT: Item<u64> = Item::new("reserve_history_count");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct DailyVolume {
+    pub token1_volume: Uint128,
+    pub token2_volume: Uint128,
+    // Fees are paid in token1, split by the token that was sold
+    #[serde(alias = "token1_fees")]
+    pub fees_token1_sold: Uint128,
+    #[serde(alias = "token2_fees")]
+    pub fees_token2_sold: Uint128,
+    pub trade_count: u64,
+}
+
+// Keyed by day index (block time / 86400)
+pub const DAILY_VOLUME: Map<u64, DailyVolume> = Map::new("daily_volume");
@@ -1,10 +1,9 @@
 use cosmwasm_std::{
-    to_binary,  Response, StdResult, Uint128, Coin, BankMsg,
-    WasmMsg, WasmQuery, QueryRequest, Addr, Storage, CosmosMsg,  QuerierWrapper, BalanceResponse as NativeBalanceResponse, BankQuery
+    to_binary,  Response, Uint128, Coin, BankMsg,
+    WasmMsg, WasmQuery, QueryRequest, Addr, CosmosMsg,  QuerierWrapper, BalanceResponse as NativeBalanceResponse, BankQuery
 };
 use cw20::{Balance, Cw20ExecuteMsg, Denom, BalanceResponse as CW20BalanceResponse, Cw20QueryMsg};
 use crate::error::ContractError;
-use crate::state::CONFIG;
 // use stockpool::msg::{ExecuteMsg as WasmswapExecuteMsg, QueryMsg as WasmswapQueryMsg, Token1ForToken2PriceResponse, Token2ForToken1PriceResponse, InfoResponse as WasmswapInfoResponse, TokenSelect};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -98,6 +97,7 @@ pub enum StockQueryMsg {
     /// Only with "marketing" extension
     /// Returns more metadata on the contract to display in the client:
     /// - description, logo, project url, etc.
+    ///
     /// Return type: MarketingInfoResponse
     MarketingInfo {},
     /// Only with "marketing" extension
@@ -210,8 +210,20 @@ pub fn get_amount_of_denom(balance: Balance, denom: Denom) -> Result<Uint128, Co
             }
         }
         Denom::Cw20(cw20_address) => {
-            // Adjust logic for CW20 tokens as needed
-            // ...
+            match balance {
+                Balance::Native(_) => {
+                    return Err(ContractError::TokenTypeMismatch {});
+                }
+                Balance::Cw20(token) => {
+                    if token.address != cw20_address {
+                        return Err(ContractError::TokenTypeMismatch {});
+                    }
+                    if token.amount == Uint128::zero() {
+                        return Err(ContractError::Cw20InputZero {});
+                    }
+                    return Ok(token.amount);
+                }
+            }
         }
     }
 }
@@ -334,8 +346,14 @@ pub fn transfer_token_message(denom: Denom, amount: Uint128, recipient: Addr) ->
             .into());
         }
         Denom::Cw20(cw20_address) => {
-            // Adjust logic for CW20 tokens as needed
-            // ...
+            return Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: cw20_address.clone().into(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.clone().into(),
+                    amount,
+                })?,
+            }));
         }
     }
 }
@@ -352,8 +370,11 @@ pub fn get_token_amount(querier: QuerierWrapper, denom: Denom, contract_addr: Ad
             return Ok(native_response.amount.amount);
         }
         Denom::Cw20(cw20_address) => {
-            // Adjust logic for CW20 tokens as needed
-            // ...
+            let balance_response: CW20BalanceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: cw20_address.clone().into(),
+                msg: to_binary(&Cw20QueryMsg::Balance {address: contract_addr.clone().into()})?,
+            }))?;
+            return Ok(balance_response.balance);
         }
     }
 }